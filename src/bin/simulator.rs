@@ -0,0 +1,55 @@
+use serde_json::Value;
+use tichu_rs::{game_core::core::BotKind, simulator::Simulation};
+
+const USAGE: &str =
+    "usage: simulator [--games N] [--seed N] [--team-one BOT] [--team-two BOT] [--config JSON]";
+
+/// Plays bots against each other and prints how each team did.
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut simulation = Simulation {
+        seed: rand::random(),
+        ..Default::default()
+    };
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let value = args
+            .next()
+            .ok_or_else(|| format!("missing value for {}\n{}", arg, USAGE))?;
+
+        match arg.as_str() {
+            "--games" => simulation.games = value.parse()?,
+            "--seed" => simulation.seed = value.parse()?,
+            "--team-one" => simulation.teams[0] = bot_kind(value)?,
+            "--team-two" => simulation.teams[1] = bot_kind(value)?,
+            "--config" => simulation.config = serde_json::from_str(&value)?,
+            _ => return Err(format!("unknown argument {}\n{}", arg, USAGE).into()),
+        }
+    }
+
+    let stats = simulation.run()?;
+
+    println!(
+        "{} games with seed {}, {} rounds",
+        stats.games, simulation.seed, stats.rounds
+    );
+
+    for (team, name) in ["team one", "team two"].into_iter().enumerate() {
+        println!(
+            "{} ({:?}): won {:.1}%, {:.1} points per round, {}/{} calls made ({:.1}%)",
+            name,
+            simulation.teams[team],
+            stats.win_rate(team) * 100.0,
+            stats.average_round_score(team),
+            stats.calls_made[team],
+            stats.calls[team],
+            stats.tichu_success_rate(team) * 100.0
+        );
+    }
+
+    Ok(())
+}
+
+fn bot_kind(name: String) -> serde_json::Result<BotKind> {
+    serde_json::from_value(Value::String(name))
+}
@@ -1,135 +1,351 @@
+use std::{collections::HashMap, future::Future};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use serde_json::Value;
 use socketioxide::{
-    extract::{Data, SocketRef, State},
-    socket::Sid,
+    extract::{AckSender, Data, SocketRef, State, TryData},
+    socket::DisconnectReason,
 };
 use tracing::info;
 
 use crate::{
-    game_client::client::{connect_lobby, create_lobby},
-    game_core::core::{Action, Cards, GameStore, Turn},
+    game_client::{
+        actor::GameRegistry,
+        client::{
+            add_bot, connect_lobby, create_lobby, disconnect, exchange_cards, get_state,
+            gift_dragon, grand_tichu, pass_turn, play_turn, rejoin, remove_bot, swap_team, tichu,
+            GracePeriod,
+        },
+    },
+    game_core::core::{Cards, GameConfig, GameError, GameResult, PlayerId},
 };
 
 pub fn on_connect(socket: SocketRef, Data(_): Data<Value>) {
     info!("Socket.IO connected: {:?} {:?}", socket.ns(), socket.id);
 
+    socket.on_disconnect(
+        |socket: SocketRef,
+         reason: DisconnectReason,
+         games: State<GameRegistry>,
+         grace_period: State<GracePeriod>| {
+            info!("Socket.IO disconnected: {:?} {:?}", socket.id, reason);
+            _ = disconnect(socket, GameRegistry::clone(&games), *grace_period);
+        },
+    );
+
     socket.on(
         "connect-lobby",
-        |socket: SocketRef, Data::<Value>(data), game_store: State<GameStore>| {
-            info!("Connecting to lobby: {:?}", data);
-            _ = connect_lobby(socket, data, game_store.clone());
+        |socket: SocketRef,
+         request: TryData<Request<ConnectLobby>>,
+         ack: AckSender,
+         games: State<GameRegistry>| async move {
+            handle(ack, request, |lobby| async move {
+                info!("Connecting to lobby: {:?}", lobby);
+                connect_lobby(&socket, lobby.game_id, lobby.username, &games).await
+            })
+            .await;
         },
     );
 
     socket.on(
         "create-lobby",
-        |socket: SocketRef, Data::<String>(username), game_store: State<GameStore>| {
-            _ = create_lobby(socket, username, game_store.clone());
+        |socket: SocketRef,
+         request: TryData<Request<CreateLobby>>,
+         ack: AckSender,
+         games: State<GameRegistry>| async move {
+            handle(ack, request, |lobby| async move {
+                info!("Creating lobby: {:?}", lobby);
+                create_lobby(&socket, lobby.username, lobby.config, &games)
+            })
+            .await;
         },
     );
 
     socket.on(
         "player-swap-team",
         |socket: SocketRef,
-         Data::<PlayerSwapTeam>(player_swap_team),
-         game_store: State<GameStore>| {
-            info!("Swapping team: {:?}", player_swap_team);
-            let game_id = player_swap_team.game_id;
-            let game_store = game_store.clone();
-            let ((team_player1, position_p1), (team_player2, position_p2)) = {
-                let guard = game_store.lock().unwrap();
-                let game = guard.get(&game_id).unwrap();
-                let player1 = game
-                    .players
-                    .get(&player_swap_team.player1)
-                    .expect("Player 1 not found");
-                let player2 = game
-                    .players
-                    .get(&player_swap_team.player2)
-                    .expect("Player 2 not found");
-
-                let position_1 = player1.place;
-                let position_2 = player2.place;
-                (
-                    (player1.team.clone(), position_1),
-                    (player2.team.clone(), position_2),
+         request: TryData<Request<PlayerSwapTeam>>,
+         ack: AckSender,
+         games: State<GameRegistry>| async move {
+            handle(ack, request, |swap| async move {
+                info!("Swapping team: {:?}", swap);
+                swap_team(&socket, swap.game_id, swap.player1, swap.player2, &games).await
+            })
+            .await;
+        },
+    );
+
+    socket.on(
+        "add-bot",
+        |socket: SocketRef,
+         request: TryData<Request<GameRequest>>,
+         ack: AckSender,
+         games: State<GameRegistry>| async move {
+            handle(ack, request, |request| async move {
+                info!("Adding bot: {:?}", request);
+                add_bot(&socket, request.game_id, &games).await
+            })
+            .await;
+        },
+    );
+
+    socket.on(
+        "remove-bot",
+        |socket: SocketRef,
+         request: TryData<Request<RemoveBot>>,
+         ack: AckSender,
+         games: State<GameRegistry>| async move {
+            handle(ack, request, |remove| async move {
+                info!("Removing bot: {:?}", remove);
+                remove_bot(&socket, remove.game_id, remove.player_id, &games).await
+            })
+            .await;
+        },
+    );
+
+    socket.on(
+        "rejoin",
+        |socket: SocketRef,
+         request: TryData<Request<Rejoin>>,
+         ack: AckSender,
+         games: State<GameRegistry>| async move {
+            handle(ack, request, |rejoin_request| async move {
+                info!("Rejoining lobby: {:?}", rejoin_request.game_id);
+                rejoin(
+                    &socket,
+                    rejoin_request.game_id,
+                    rejoin_request.token,
+                    &games,
                 )
-            };
-
-            let mut guard = game_store.lock().unwrap();
-            let game = guard.get_mut(&game_id).unwrap();
-            game.players
-                .get_mut(&player_swap_team.player1)
-                .unwrap()
-                .team = team_player2;
-            game.players
-                .get_mut(&player_swap_team.player2)
-                .unwrap()
-                .team = team_player1;
-
-            game.players
-                .get_mut(&player_swap_team.player1)
-                .unwrap()
-                .place = position_p2;
-            game.players
-                .get_mut(&player_swap_team.player2)
-                .unwrap()
-                .place = position_p1;
-
-            let players = game.players.values().cloned().collect::<Vec<_>>();
-            socket.emit("users-in-lobby", players).unwrap();
+                .await
+            })
+            .await;
+        },
+    );
+
+    socket.on(
+        "grand-tichu",
+        |socket: SocketRef,
+         request: TryData<Request<GrandTichu>>,
+         ack: AckSender,
+         games: State<GameRegistry>| async move {
+            handle(ack, request, |grand| async move {
+                info!("Grand tichu decision: {:?}", grand);
+                grand_tichu(&socket, grand.game_id, grand.call, &games).await
+            })
+            .await;
+        },
+    );
+
+    socket.on(
+        "tichu",
+        |socket: SocketRef,
+         request: TryData<Request<GameRequest>>,
+         ack: AckSender,
+         games: State<GameRegistry>| async move {
+            handle(ack, request, |request| async move {
+                info!("Calling tichu: {:?}", request);
+                tichu(&socket, request.game_id, &games).await
+            })
+            .await;
+        },
+    );
+
+    socket.on(
+        "exchange-cards",
+        |socket: SocketRef,
+         request: TryData<Request<ExchangeCards>>,
+         ack: AckSender,
+         games: State<GameRegistry>| async move {
+            handle(ack, request, |exchange| async move {
+                info!("Exchanging cards: {:?}", exchange);
+                exchange_cards(&socket, exchange.game_id, exchange.player_card, &games).await
+            })
+            .await;
+        },
+    );
+
+    socket.on(
+        "gift-dragon",
+        |socket: SocketRef,
+         request: TryData<Request<GiftDragon>>,
+         ack: AckSender,
+         games: State<GameRegistry>| async move {
+            handle(ack, request, |gift| async move {
+                info!("Gifting dragon: {:?}", gift);
+                gift_dragon(&socket, gift.game_id, gift.recipient, &games).await
+            })
+            .await;
+        },
+    );
+
+    socket.on(
+        "get-state",
+        |socket: SocketRef,
+         request: TryData<Request<GameRequest>>,
+         ack: AckSender,
+         games: State<GameRegistry>| async move {
+            handle(ack, request, |request| async move {
+                info!("Getting state: {:?}", request);
+                get_state(&socket, request.game_id, &games).await
+            })
+            .await;
+        },
+    );
+
+    socket.on(
+        "pass-turn",
+        |socket: SocketRef,
+         request: TryData<Request<GameRequest>>,
+         ack: AckSender,
+         games: State<GameRegistry>| async move {
+            handle(ack, request, |request| async move {
+                info!("Passing turn: {:?}", request);
+                pass_turn(&socket, request.game_id, &games).await
+            })
+            .await;
         },
     );
 
     socket.on(
         "play-turn",
-        |socket: SocketRef, Data::<PlayTurn>(playturn), game_store: State<GameStore>| {
-            info!("Playing turn: {:?}", playturn);
-            let game_id = playturn.game_id;
-            let game_store = game_store.clone();
-            let mut guard = game_store.lock().unwrap();
-            let game = guard.get_mut(&game_id).unwrap();
-
-            let turn = Turn {
-                player: socket.id,
-                action: Action::Play,
-                cards: Some(playturn.cards),
-            };
-
-            match game.play_turn(turn) {
-                Ok(_) => {
-                    //handle round end
-                    socket
-                        .emit(
-                            "trick-played",
-                            game.round.as_ref().unwrap().current_trick.clone(),
-                        )
-                        .unwrap();
-                    let next_player = game.round.as_ref().unwrap().current_player;
-                    socket.emit("next-player", next_player).unwrap();
-                    info!(
-                        "Next user: {:?}",
-                        game.players.get(&next_player).unwrap().username
-                    );
-                }
-                Err(err) => {
-                    socket.emit("trick-error", format!("{}", err)).unwrap();
-                }
-            }
+        |socket: SocketRef,
+         request: TryData<Request<PlayTurn>>,
+         ack: AckSender,
+         games: State<GameRegistry>| async move {
+            handle(ack, request, |playturn| async move {
+                info!("Playing turn: {:?}", playturn);
+                play_turn(&socket, playturn.game_id, playturn.cards, &games).await
+            })
+            .await;
         },
     );
 }
 
-#[derive(Debug, serde::Deserialize)]
+/// Runs a command with its parsed body and acknowledges its result. A command that can not be
+/// parsed is rejected without running it.
+async fn handle<T, R, F, Fut>(ack: AckSender, TryData(request): TryData<Request<T>>, command: F)
+where
+    T: DeserializeOwned,
+    R: Serialize,
+    F: FnOnce(T) -> Fut,
+    Fut: Future<Output = GameResult<R>>,
+{
+    match request {
+        Ok(request) => {
+            let result = command(request.body).await;
+            reply(ack, request.request_id, result);
+        }
+        Err(err) => reject(ack, err),
+    }
+}
+
+/// Acknowledges a command with `{ ok, data | error, requestId }`.
+fn reply<T: Serialize>(ack: AckSender, request_id: Option<String>, result: GameResult<T>) {
+    let ack_data = match result {
+        Ok(data) => Ack {
+            ok: true,
+            data: Some(data),
+            error: None,
+            request_id,
+        },
+        Err(err) => {
+            info!("Command failed: {}", err);
+            Ack {
+                ok: false,
+                data: None,
+                error: Some(err),
+                request_id,
+            }
+        }
+    };
+
+    ack.send(ack_data).ok();
+}
+
+/// Acknowledges a command that could not be parsed, so its request id is not known either.
+fn reject(ack: AckSender, err: serde_json::Error) {
+    reply::<()>(ack, None, Err(GameError::InvalidRequest(err.to_string())));
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Ack<T> {
+    ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    data: Option<T>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<GameError>,
+    request_id: Option<String>,
+}
+
+/// Every command may carry a request id, which is echoed in its acknowledgement.
+#[derive(Debug, Deserialize)]
+struct Request<T> {
+    #[serde(rename = "requestId", default)]
+    request_id: Option<String>,
+    #[serde(flatten)]
+    body: T,
+}
+
+#[derive(Debug, Deserialize)]
+struct GameRequest {
+    game_id: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct CreateLobby {
+    username: String,
+    #[serde(default)]
+    config: GameConfig,
+}
+
+#[derive(Debug, Deserialize)]
+struct ConnectLobby {
+    game_id: String,
+    username: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct RemoveBot {
+    game_id: String,
+    player_id: PlayerId,
+}
+
+#[derive(Debug, Deserialize)]
 struct PlayTurn {
     game_id: String,
     cards: Vec<Cards>,
 }
 
-#[derive(Debug, serde::Deserialize)]
+#[derive(Debug, Deserialize)]
+struct Rejoin {
+    game_id: String,
+    token: uuid::Uuid,
+}
+
+#[derive(Debug, Deserialize)]
+struct GiftDragon {
+    game_id: String,
+    recipient: PlayerId,
+}
+
+#[derive(Debug, Deserialize)]
+struct GrandTichu {
+    game_id: String,
+    call: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct ExchangeCards {
+    game_id: String,
+    player_card: HashMap<PlayerId, Cards>,
+}
+
+#[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct PlayerSwapTeam {
     game_id: String,
-    player1: Sid,
-    player2: Sid,
+    player1: PlayerId,
+    player2: PlayerId,
 }
@@ -0,0 +1,220 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, OnceLock, PoisonError, RwLock},
+    time::Duration,
+};
+
+use anyhow::Context;
+use socketioxide::SocketIo;
+use tokio::{
+    sync::{mpsc, oneshot},
+    time::Instant,
+};
+use tracing::info;
+
+use crate::{
+    game_client::{client::play_bots, timer::Timer},
+    game_core::{
+        core::{Game, GameError, GameResult},
+        log::Event,
+    },
+};
+
+type Job = Box<dyn FnOnce(&mut Game, &SocketIo) + Send>;
+type Games = Arc<RwLock<HashMap<String, GameHandle>>>;
+type Logs = Arc<RwLock<HashMap<String, Vec<Event>>>>;
+
+//finished games are kept for a while, so players can still look at the result
+const FINISHED_GAME_RETENTION: Duration = Duration::from_secs(10 * 60);
+
+/// Handle to the task that owns a game. Every read or change of the game is sent through it,
+/// so actions on one game never wait on another.
+#[derive(Clone)]
+pub struct GameHandle {
+    jobs: mpsc::UnboundedSender<Job>,
+}
+
+impl GameHandle {
+    fn spawn(mut game: Game, io: SocketIo, games: Games, logs: Logs) -> Self {
+        let (jobs, mut receiver) = mpsc::unbounded_channel::<Job>();
+
+        tokio::spawn(async move {
+            let mut timer = Timer::default();
+            let mut ticks = tokio::time::interval(Duration::from_secs(1));
+            let mut finished_at = None;
+
+            loop {
+                tokio::select! {
+                    job = receiver.recv() => match job {
+                        Some(job) => job(&mut game, &io),
+                        None => break,
+                    },
+                    _ = ticks.tick() => timer.tick(&mut game, &io),
+                }
+
+                play_bots(&mut game, &io);
+
+                if is_done(&game, &mut finished_at) {
+                    break;
+                }
+            }
+
+            //the log of a finished game outlives its task
+            if game.winner.is_some() {
+                logs.write()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .insert(game.game_id.clone(), std::mem::take(&mut game.log));
+            }
+
+            games
+                .write()
+                .unwrap_or_else(PoisonError::into_inner)
+                .remove(&game.game_id);
+            info!("game {} stopped", game.game_id);
+        });
+
+        GameHandle { jobs }
+    }
+
+    /// Runs `f` inside the task of the game and waits for its result.
+    pub async fn call<T, F>(&self, f: F) -> GameResult<T>
+    where
+        F: FnOnce(&mut Game, &SocketIo) -> GameResult<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let (sender, receiver) = oneshot::channel();
+
+        self.jobs
+            .send(Box::new(move |game, io| {
+                sender.send(f(game, io)).ok();
+            }))
+            .map_err(|_| GameError::NotFound)?;
+
+        //the task is gone if the job panicked
+        receiver.await.map_err(|_| GameError::NotFound)?
+    }
+
+    fn is_stopped(&self) -> bool {
+        self.jobs.is_closed()
+    }
+}
+
+/// Whether the task of the game can stop, because the lobby has no one left in it or the game
+/// has been over for long enough.
+fn is_done(game: &Game, finished_at: &mut Option<Instant>) -> bool {
+    if game.phase.is_none() && game.players.values().all(|p| p.bot.is_some()) {
+        return true;
+    }
+
+    if game.winner.is_none() {
+        return false;
+    }
+
+    finished_at.get_or_insert_with(Instant::now).elapsed() >= FINISHED_GAME_RETENTION
+}
+
+/// Maps game ids to the handles of their running games, and to the logs of finished games
+/// whose task has stopped.
+#[derive(Clone, Default)]
+pub struct GameRegistry {
+    games: Games,
+    logs: Logs,
+    io: Arc<OnceLock<SocketIo>>,
+}
+
+impl GameRegistry {
+    /// Sets the socket.io instance the games emit their events through.
+    /// Needs to be called once before the first game is inserted.
+    pub fn bind(&self, io: SocketIo) {
+        self.io.set(io).ok();
+    }
+
+    /// Starts the task of the game and registers its handle.
+    pub fn insert(&self, game: Game) -> GameResult<GameHandle> {
+        let io = self.io.get().cloned().context("socket.io is not bound")?;
+        let game_id = game.game_id.clone();
+        let handle = GameHandle::spawn(game, io, self.games.clone(), self.logs.clone());
+
+        self.games
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(game_id, handle.clone());
+
+        Ok(handle)
+    }
+
+    pub fn get(&self, game_id: &str) -> GameResult<GameHandle> {
+        self.games
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(game_id)
+            .filter(|handle| !handle.is_stopped())
+            .cloned()
+            .ok_or(GameError::NotFound)
+    }
+
+    /// The log of a finished game, once its task has stopped.
+    pub fn finished_log(&self, game_id: &str) -> GameResult<Vec<Event>> {
+        self.logs
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(game_id)
+            .cloned()
+            .ok_or(GameError::NotFound)
+    }
+
+    /// The task of the game stops once the last handle to it is dropped.
+    pub fn remove(&self, game_id: &str) {
+        self.games
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(game_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, time::Duration};
+
+    use socketioxide::SocketIo;
+
+    use crate::game_core::{
+        core::{Game, GameError, Phase, Team},
+        log::Event,
+    };
+
+    use super::GameRegistry;
+
+    #[tokio::test]
+    async fn test_finished_log_outlives_task() {
+        let (_, io) = SocketIo::new_layer();
+        io.ns("/", || {});
+
+        let games = GameRegistry::default();
+        games.bind(io);
+
+        let mut game = Game::new("finished".to_string(), HashMap::new());
+        game.phase = Some(Phase::Playing);
+        game.winner = Some(Team::One);
+        game.log.push(Event::GameOver(game.summary().unwrap()));
+        let log = game.log.clone();
+
+        games.insert(game).unwrap();
+        assert!(matches!(
+            games.finished_log("finished"),
+            Err(GameError::NotFound)
+        ));
+
+        //the task stops once the registry lets go of its handle
+        games.remove("finished");
+        for _ in 0..100 {
+            if games.finished_log("finished").is_ok() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        assert!(matches!(games.get("finished"), Err(GameError::NotFound)));
+        assert_eq!(games.finished_log("finished").unwrap(), log);
+    }
+}
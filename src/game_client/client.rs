@@ -1,111 +1,762 @@
-use anyhow::Result;
-use serde::Deserialize;
-use serde_json::Value;
-use socketioxide::extract::SocketRef;
+use std::{collections::HashMap, time::Duration};
+
+use anyhow::Context;
+use serde::Serialize;
+use socketioxide::{extract::SocketRef, socket::Sid, SocketIo};
 use tracing::info;
+use uuid::Uuid;
+
+use crate::{
+    game_client::actor::GameRegistry,
+    game_core::bot::{self, BotAction},
+    game_core::core::{
+        Action, BotKind, Call, Cards, Exchange, Game, GameConfig, GameError, GameResult, GameView,
+        Phase, Player, PlayerId, Team, Turn,
+    },
+};
 
-use crate::game_core::core::{Game, GameStore, Player, Team};
+/// How long the seat of a disconnected player is held before they are given up on.
+#[derive(Debug, Clone, Copy)]
+pub struct GracePeriod(pub Duration);
 
-#[derive(Debug, Deserialize)]
-struct JoinLobbyDto {
+/// Sent only to the player, the token is needed to take the seat back after reconnecting.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Session {
     game_id: String,
-    username: String,
+    player_id: PlayerId,
+    token: Uuid,
 }
 
-pub fn create_lobby(socket: SocketRef, username: String, game_store: GameStore) -> Result<()> {
+impl Session {
+    fn new(game_id: String, player: &Player) -> Self {
+        Session {
+            game_id,
+            player_id: player.id,
+            token: player.token,
+        }
+    }
+}
+
+pub fn create_lobby(
+    socket: &SocketRef,
+    username: String,
+    config: GameConfig,
+    games: &GameRegistry,
+) -> GameResult<Session> {
+    config.validate()?;
+
     //in debug mode use GAME_ID to test otherwise generate a new game_id
     let game_id = uuid::Uuid::new_v4().to_string();
 
     let new_player = Player {
+        id: PlayerId::new_v4(),
         socket_id: socket.id,
+        token: Uuid::new_v4(),
         username,
         is_host: true,
         team: Some(Team::One),
+        place: 1,
         ..Default::default()
     };
 
-    if game_store.lock().unwrap().contains_key(&game_id) {
-        info!("Lobby already exists");
-        socket.emit("join-lobby", game_id)?;
+    let mut player_map = std::collections::HashMap::new();
+
+    player_map.insert(new_player.id, new_player.clone());
+
+    let mut game = Game::new(game_id.clone(), player_map);
+    game.configure(config.clone())?;
+
+    games.insert(game)?;
+    socket
+        .join(game_id.clone())
+        .context("failed joining the game room")?;
+    socket.emit("lobby-created", game_id.clone()).ok();
+    socket.emit("game-config", config).ok();
+
+    let session = Session::new(game_id, &new_player);
+    socket.emit("session", &session).ok();
+    Ok(session)
+}
+
+pub async fn connect_lobby(
+    socket: &SocketRef,
+    game_id: String,
+    username: String,
+    games: &GameRegistry,
+) -> GameResult<Session> {
+    let game = games.get(&game_id).inspect_err(|_| {
+        info!("Lobby does not exist");
+    })?;
+
+    let socket_id = socket.id;
+    let (new_player, players, config) = game
+        .call(move |game, _| {
+            let new_player = game.join_lobby(username, socket_id)?;
+            info!("New player: {:?}", new_player.view());
+
+            let players = game.players.values().map(|p| p.view()).collect::<Vec<_>>();
+            Ok((new_player, players, game.config.clone()))
+        })
+        .await?;
+
+    socket
+        .join(game_id.clone())
+        .context("failed joining the game room")?;
+
+    let session = Session::new(game_id.clone(), &new_player);
+    socket.emit("session", &session).ok();
+
+    // emit to all users in the new user that joined
+    socket
+        .to(game_id)
+        .emit("user-joined", new_player.view())
+        .ok();
+
+    //emit to the new user all the users in the lobby
+    info!("Players in lobby: {:?}", players);
+    socket.emit("users-in-lobby", players).ok();
+    socket.emit("game-config", config).ok();
+
+    Ok(session)
+}
+
+pub async fn exchange_cards(
+    socket: &SocketRef,
+    game_id: String,
+    player_card: HashMap<PlayerId, Cards>,
+    games: &GameRegistry,
+) -> GameResult<()> {
+    let socket_id = socket.id;
+
+    games
+        .get(&game_id)?
+        .call(move |game, io| {
+            let player_id = game.player_id(socket_id)?;
+
+            let exchange = Exchange {
+                player: player_id,
+                player_card,
+            };
+
+            let all_exchanged = game.submit_exchange(exchange)?;
+
+            exchange_submitted(io, game, player_id, all_exchanged)
+        })
+        .await
+}
+
+/// Lets the room know a player chose their cards and swaps them once everyone did.
+pub(super) fn exchange_submitted(
+    io: &SocketIo,
+    game: &mut Game,
+    player_id: PlayerId,
+    all_exchanged: bool,
+) -> GameResult<()> {
+    let game_id = game.game_id.clone();
+
+    io.within(game_id.clone())
+        .emit("exchange-submitted", player_id)
+        .ok();
+
+    if !all_exchanged {
         return Ok(());
     }
-    let mut player_map = std::collections::HashMap::new();
 
-    player_map.insert(socket.id, new_player.clone());
+    //the received cards are handed out before anyone learns that the play begins
+    let received = game.exchange_cards()?;
 
-    game_store.lock().unwrap().insert(
-        game_id.clone(),
-        Game {
-            game_id: game_id.clone(),
-            players: player_map,
-            ..Default::default()
-        },
-    );
-    socket.join(game_id.clone())?;
-    socket.emit("lobby-created", game_id)?;
-    Ok(())
+    for (player_id, cards) in received {
+        let player = game
+            .players
+            .get(&player_id)
+            .context("failed getting player")?;
+
+        emit_to(io, player.socket_id, "exchange-received", cards);
+        emit_to(io, player.socket_id, "hand", player.hand.clone());
+    }
+
+    let phase = game.start_playing()?;
+    phase_changed(io, game, phase)
 }
 
-pub fn connect_lobby(socket: SocketRef, data: Value, game_store: GameStore) -> Result<()> {
-    info!("Connecting to lobby: {:?}", data);
-    let data: JoinLobbyDto = serde_json::from_value(data)?;
-    let game_id = data.game_id;
+/// Announces the new phase, and the first player once the cards are played.
+pub(crate) fn phase_changed(io: &SocketIo, game: &Game, phase: Phase) -> GameResult<()> {
+    let game_id = game.game_id.clone();
+    let playing = matches!(phase, Phase::Playing);
 
-    if !game_store.lock().unwrap().contains_key(&game_id) {
-        info!("Lobby does not exist");
-        socket.emit("lobby-not-found", game_id)?;
+    io.within(game_id.clone()).emit("game-phase", phase).ok();
+
+    if !playing {
         return Ok(());
     }
 
-    socket.join(game_id.clone())?;
+    let player_turn = game
+        .round
+        .as_ref()
+        .context("failed getting round")?
+        .current_player;
 
-    let player_count = game_store
-        .lock()
-        .unwrap()
-        .get(&game_id)
-        .unwrap()
+    io.within(game_id.clone()).emit("started", "").ok();
+    io.within(game_id).emit("next-player", player_turn).ok();
+
+    Ok(())
+}
+
+pub async fn grand_tichu(
+    socket: &SocketRef,
+    game_id: String,
+    call: bool,
+    games: &GameRegistry,
+) -> GameResult<()> {
+    let socket_id = socket.id;
+
+    games
+        .get(&game_id)?
+        .call(move |game, io| {
+            let player_id = game.player_id(socket_id)?;
+
+            let all_decided = game.call_grand_tichu(player_id, call)?;
+
+            grand_tichu_decided(io, game, player_id, call, all_decided)
+        })
+        .await
+}
+
+/// Hands the player their full hand and moves on to the exchange once everyone decided.
+pub(super) fn grand_tichu_decided(
+    io: &SocketIo,
+    game: &mut Game,
+    player_id: PlayerId,
+    call: bool,
+    all_decided: bool,
+) -> GameResult<()> {
+    let game_id = game.game_id.clone();
+    let player = game
         .players
-        .len() as u8;
+        .get(&player_id)
+        .context("failed getting player")?;
+    emit_to(io, player.socket_id, "hand", player.hand.clone());
 
-    let team = if player_count % 2 == 0 {
-        Some(Team::One)
-    } else {
-        Some(Team::Two)
-    };
+    if call {
+        io.within(game_id.clone())
+            .emit("tichu-called", (player_id, Call::GrandTichu))
+            .ok();
+    }
 
-    let new_player = Player {
-        socket_id: socket.id,
-        username: data.username,
-        place: player_count + 1,
-        team,
-        ..Default::default()
-    };
+    if all_decided {
+        let phase = game.end_grand_tichu()?;
+        return phase_changed(io, game, phase);
+    }
+
+    Ok(())
+}
+
+pub async fn tichu(socket: &SocketRef, game_id: String, games: &GameRegistry) -> GameResult<()> {
+    let socket_id = socket.id;
+
+    games
+        .get(&game_id)?
+        .call(move |game, io| {
+            let player_id = game.player_id(socket_id)?;
+
+            game.call_tichu(player_id)?;
+
+            io.within(game_id)
+                .emit("tichu-called", (player_id, Call::Tichu))
+                .ok();
+
+            Ok(())
+        })
+        .await
+}
+
+pub async fn play_turn(
+    socket: &SocketRef,
+    game_id: String,
+    cards: Vec<Cards>,
+    games: &GameRegistry,
+) -> GameResult<()> {
+    let socket_id = socket.id;
 
-    info!("New player: {:?}", new_player);
-    game_store
-        .lock()
-        .unwrap()
-        .get_mut(&game_id)
-        .unwrap()
+    games
+        .get(&game_id)?
+        .call(move |game, io| {
+            let player_id = game.player_id(socket_id)?;
+
+            let interrupting = game
+                .round
+                .as_ref()
+                .is_some_and(|round| round.current_player != player_id);
+
+            let turn = Turn {
+                player: player_id,
+                action: Action::Play,
+                cards: Some(cards.clone()),
+            };
+
+            let trick_over = game.play_turn(turn.clone())?;
+
+            if interrupting {
+                io.within(game_id.clone())
+                    .emit("bomb-played", (player_id, cards))
+                    .ok();
+            }
+
+            trick_played(io, game, &turn, trick_over)
+        })
+        .await
+}
+
+/// Sends the player what is left of their hand and lets the room know about the new trick.
+pub(super) fn trick_played(
+    io: &SocketIo,
+    game: &mut Game,
+    turn: &Turn,
+    trick_over: bool,
+) -> GameResult<()> {
+    let game_id = game.game_id.clone();
+    let player = game
         .players
-        .insert(socket.id, new_player.clone());
+        .get(&turn.player)
+        .context("failed getting player")?;
+    emit_to(io, player.socket_id, "hand", player.hand.clone());
+
+    //the dog leaves no trick on the table, so it is announced on its own
+    if matches!(turn.cards.as_deref(), Some([Cards::Dog])) {
+        io.within(game_id.clone())
+            .emit("dog-played", turn.player)
+            .ok();
+    } else {
+        let trick_state = game
+            .round
+            .as_ref()
+            .context("failed getting round")?
+            .trick_state();
+        io.within(game_id.clone())
+            .emit("trick-played", trick_state)
+            .ok();
+    }
+
+    if trick_over {
+        trick_won(io, game);
+    }
+
+    if game.round_over() {
+        return finish_round(io, game);
+    }
+
+    let round = game.round.as_ref().context("failed getting round")?;
+
+    if round.dragon_gift.is_none() {
+        io.within(game_id)
+            .emit("next-player", round.current_player)
+            .ok();
+    }
+
+    Ok(())
+}
+
+pub async fn pass_turn(
+    socket: &SocketRef,
+    game_id: String,
+    games: &GameRegistry,
+) -> GameResult<()> {
+    let socket_id = socket.id;
+
+    games
+        .get(&game_id)?
+        .call(move |game, io| {
+            let player_id = game.player_id(socket_id)?;
+
+            let turn = Turn {
+                player: player_id,
+                action: Action::Pass,
+                cards: None,
+            };
+
+            let trick_over = game.play_turn(turn)?;
+
+            turn_passed(io, game, player_id, trick_over)
+        })
+        .await
+}
+
+/// Lets the room know the player passed and who is next.
+pub(super) fn turn_passed(
+    io: &SocketIo,
+    game: &mut Game,
+    player_id: PlayerId,
+    trick_over: bool,
+) -> GameResult<()> {
+    let game_id = game.game_id.clone();
+
+    io.within(game_id.clone())
+        .emit("turn-passed", player_id)
+        .ok();
+
+    if trick_over {
+        let trick_state = game
+            .round
+            .as_ref()
+            .context("failed getting round")?
+            .trick_state();
+        io.within(game_id.clone())
+            .emit("trick-played", trick_state)
+            .ok();
+        trick_won(io, game);
+    }
+
+    if game.round_over() {
+        return finish_round(io, game);
+    }
+
+    let round = game.round.as_ref().context("failed getting round")?;
+
+    //the next player is only known once the dragon is given away
+    if round.dragon_gift.is_none() {
+        io.within(game_id)
+            .emit("next-player", round.current_player)
+            .ok();
+    }
+
+    Ok(())
+}
+
+pub async fn gift_dragon(
+    socket: &SocketRef,
+    game_id: String,
+    recipient: PlayerId,
+    games: &GameRegistry,
+) -> GameResult<()> {
+    let socket_id = socket.id;
+
+    games
+        .get(&game_id)?
+        .call(move |game, io| {
+            let player_id = game.player_id(socket_id)?;
+
+            game.gift_dragon(player_id, recipient)?;
+
+            dragon_gifted(io, game, player_id, recipient)
+        })
+        .await
+}
+
+/// Lets the room know who received the dragon trick.
+pub(super) fn dragon_gifted(
+    io: &SocketIo,
+    game: &mut Game,
+    player_id: PlayerId,
+    recipient: PlayerId,
+) -> GameResult<()> {
+    io.within(game.game_id.clone())
+        .emit("dragon-gifted", (player_id, recipient))
+        .ok();
+
+    trick_won(io, game);
+
+    if game.round_over() {
+        finish_round(io, game)?;
+    }
+
+    Ok(())
+}
+
+/// Swaps the seats of two players in the lobby. Only the host can swap teams.
+pub async fn swap_team(
+    socket: &SocketRef,
+    game_id: String,
+    player1: PlayerId,
+    player2: PlayerId,
+    games: &GameRegistry,
+) -> GameResult<()> {
+    let socket_id = socket.id;
+
+    games
+        .get(&game_id)?
+        .call(move |game, io| {
+            check_host(game, socket_id)?;
+
+            if game.phase.is_some() {
+                return Err(GameError::WrongPhase(
+                    "teams can only be swapped in the lobby".into(),
+                ));
+            }
+
+            let (team_1, place_1) = game
+                .players
+                .get(&player1)
+                .map(|p| (p.team.clone(), p.place))
+                .ok_or(GameError::PlayerNotFound)?;
+            let (team_2, place_2) = game
+                .players
+                .get(&player2)
+                .map(|p| (p.team.clone(), p.place))
+                .ok_or(GameError::PlayerNotFound)?;
+
+            let player = game.players.get_mut(&player1).unwrap();
+            player.team = team_2;
+            player.place = place_2;
+
+            let player = game.players.get_mut(&player2).unwrap();
+            player.team = team_1;
+            player.place = place_1;
+
+            let players = game.players.values().map(|p| p.view()).collect::<Vec<_>>();
+            io.within(game_id).emit("users-in-lobby", players).ok();
+
+            Ok(())
+        })
+        .await
+}
+
+/// Seats a bot in the lobby. Only the host can add bots.
+pub async fn add_bot(socket: &SocketRef, game_id: String, games: &GameRegistry) -> GameResult<()> {
+    let socket_id = socket.id;
+
+    games
+        .get(&game_id)?
+        .call(move |game, io| {
+            check_host(game, socket_id)?;
+            game.add_bot(BotKind::Rules)?;
+
+            let players = game.players.values().map(|p| p.view()).collect::<Vec<_>>();
+            io.within(game_id).emit("users-in-lobby", players).ok();
+
+            Ok(())
+        })
+        .await
+}
+
+pub async fn remove_bot(
+    socket: &SocketRef,
+    game_id: String,
+    player_id: PlayerId,
+    games: &GameRegistry,
+) -> GameResult<()> {
+    let socket_id = socket.id;
+
+    games
+        .get(&game_id)?
+        .call(move |game, io| {
+            check_host(game, socket_id)?;
+            game.remove_bot(player_id)?;
+
+            let players = game.players.values().map(|p| p.view()).collect::<Vec<_>>();
+            io.within(game_id).emit("users-in-lobby", players).ok();
+
+            Ok(())
+        })
+        .await
+}
+
+fn check_host(game: &Game, socket_id: Sid) -> GameResult<()> {
+    let player_id = game.player_id(socket_id)?;
+
+    match game.players.get(&player_id) {
+        Some(player) if player.is_host => Ok(()),
+        _ => Err(GameError::NotHost),
+    }
+}
+
+/// Lets the bots act until the game waits on a human again.
+pub(super) fn play_bots(game: &mut Game, io: &SocketIo) {
+    while let Some(action) = bot::next_action(game) {
+        if let Err(err) = play_bot(game, io, &action) {
+            info!("bot failed to play {:?}: {}", action, err);
+            return;
+        }
+    }
+}
+
+fn play_bot(game: &mut Game, io: &SocketIo, action: &BotAction) -> GameResult<()> {
+    let done = action.apply(game)?;
+
+    match action {
+        BotAction::GrandTichu { player, call } => {
+            grand_tichu_decided(io, game, *player, *call, done)
+        }
+        BotAction::Tichu(player) => {
+            io.within(game.game_id.clone())
+                .emit("tichu-called", (*player, Call::Tichu))
+                .ok();
+            Ok(())
+        }
+        BotAction::Exchange(exchange) => exchange_submitted(io, game, exchange.player, done),
+        BotAction::Turn(turn) => match turn.action {
+            Action::Play => trick_played(io, game, turn, done),
+            Action::Pass => turn_passed(io, game, turn.player, done),
+        },
+        BotAction::GiftDragon { player, recipient } => dragon_gifted(io, game, *player, *recipient),
+    }
+}
+
+/// Emits to a single socket, as long as it is still connected.
+fn emit_to<T: Serialize>(io: &SocketIo, socket_id: Sid, event: &'static str, data: T) {
+    match io.get_socket(socket_id) {
+        Some(socket) => {
+            socket.emit(event, data).ok();
+        }
+        None => {
+            info!("socket {} not found", socket_id);
+        }
+    }
+}
+
+/// Lets the room know who collected the last trick and how many points it was worth.
+fn trick_won(io: &SocketIo, game: &Game) {
+    let trick_won = game.round.as_ref().and_then(|r| r.trick_won.clone());
+
+    if let Some(trick_won) = trick_won {
+        io.within(game.game_id.clone())
+            .emit("trick-won", trick_won)
+            .ok();
+    }
+}
+
+/// Announces the score of the finished round and either ends the game or deals the next round.
+fn finish_round(io: &SocketIo, game: &mut Game) -> GameResult<()> {
+    let game_id = game.game_id.clone();
+    let summary = game.round_summary().cloned().context("round is not over")?;
+
+    io.within(game_id.clone()).emit("round-over", summary).ok();
+
+    if let Some(summary) = game.summary() {
+        io.within(game_id).emit("game-over", summary).ok();
+        return Ok(());
+    }
+
+    let phase = game.next_round()?;
+
+    for player in game.players.values() {
+        emit_to(io, player.socket_id, "hand", player.hand.clone());
+    }
+
+    phase_changed(io, game, phase)
+}
+
+pub async fn get_state(
+    socket: &SocketRef,
+    game_id: String,
+    games: &GameRegistry,
+) -> GameResult<GameView> {
+    let socket_id = socket.id;
+
+    games
+        .get(&game_id)?
+        .call(move |game, _| {
+            let player_id = game.player_id(socket_id)?;
+
+            game.view_for(player_id)
+        })
+        .await
+}
+
+/// Gives the seat belonging to the session token to the socket of a reconnecting player.
+pub async fn rejoin(
+    socket: &SocketRef,
+    game_id: String,
+    token: Uuid,
+    games: &GameRegistry,
+) -> GameResult<Session> {
+    let socket_id = socket.id;
+
+    let (session, view) = games
+        .get(&game_id)?
+        .call(move |game, _| {
+            let player_id = game.rejoin(token, socket_id)?;
+
+            let player = game
+                .players
+                .get(&player_id)
+                .context("failed getting player")?;
+
+            let session = Session::new(game.game_id.clone(), player);
+            Ok((session, game.view_for(player_id)?))
+        })
+        .await?;
 
-    // emit to all users in the new user that joined
     socket
-        .to(game_id.clone())
-        .emit("user-joined", &new_player)
-        .expect("Failed to emit");
+        .join(game_id.clone())
+        .context("failed joining the game room")?;
 
-    //emit to the new user all the users in the lobby
-    let game_guard = game_store.lock().unwrap();
+    socket.emit("session", &session).ok();
+    socket.emit("game-state", view).ok();
+
+    socket
+        .to(game_id)
+        .emit("player-rejoined", session.player_id)
+        .ok();
+
+    Ok(session)
+}
+
+/// Marks the player as away and gives up on them if they do not rejoin within the grace period.
+/// In the lobby the player simply leaves, a running game is aborted.
+pub fn disconnect(
+    socket: SocketRef,
+    games: GameRegistry,
+    grace_period: GracePeriod,
+) -> GameResult<()> {
+    //a socket only joins the rooms of its games, which are named after the game id
+    let rooms = socket.rooms().context("failed getting rooms of socket")?;
+    let socket_id = socket.id;
+
+    for room in rooms {
+        let game_id = room.to_string();
+        let games = games.clone();
+
+        tokio::spawn(async move {
+            let game = match games.get(&game_id) {
+                Ok(game) => game,
+                Err(_) => return,
+            };
+
+            let player_id = game
+                .call(move |game, io| {
+                    let player_id = game.mark_away(socket_id)?;
+                    io.within(game.game_id.clone())
+                        .emit("player-away", player_id)
+                        .ok();
+                    Ok(player_id)
+                })
+                .await;
+
+            let player_id = match player_id {
+                Ok(player_id) => player_id,
+                Err(_) => return,
+            };
+
+            tokio::time::sleep(grace_period.0).await;
+
+            let aborted = game
+                .call(move |game, io| {
+                    if !game.still_away(player_id, socket_id) {
+                        return Ok(false);
+                    }
+
+                    info!("player {} did not come back", player_id);
+
+                    let game_id = game.game_id.clone();
 
-    let game = game_guard.get(&game_id);
+                    //a lobby left with bots only stops on its own
+                    if game.phase.is_none() {
+                        game.leave_lobby(player_id)?;
+                        let players = game.players.values().map(|p| p.view()).collect::<Vec<_>>();
+                        io.within(game_id).emit("users-in-lobby", players).ok();
+                        return Ok(false);
+                    }
 
-    if let Some(game) = game {
-        let players = game.players.values().collect::<Vec<_>>();
-        info!("Players in lobby: {:?}", players);
+                    io.within(game_id).emit("game-aborted", player_id).ok();
+                    Ok(true)
+                })
+                .await;
 
-        socket.emit("users-in-lobby", players)?;
+            if let Ok(true) = aborted {
+                games.remove(&game_id);
+            }
+        });
     }
 
     Ok(())
@@ -1 +1,3 @@
+pub mod actor;
 pub mod client;
+pub mod timer;
@@ -0,0 +1,259 @@
+use serde::Serialize;
+use socketioxide::SocketIo;
+use tracing::info;
+
+use crate::{
+    game_client::client::{
+        dragon_gifted, exchange_submitted, grand_tichu_decided, trick_played, turn_passed,
+    },
+    game_core::core::{Action, Game, GameResult, Phase, PlayerId},
+};
+
+/// The action the table is waiting for.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase", tag = "action")]
+enum Pending {
+    GrandTichu,
+    Exchange,
+    Turn { player: PlayerId },
+    DragonGift { player: PlayerId },
+}
+
+impl Pending {
+    fn of(game: &Game) -> Option<Pending> {
+        match game.phase {
+            Some(Phase::GrandTichu) => Some(Pending::GrandTichu),
+            Some(Phase::Exchanging) => Some(Pending::Exchange),
+            Some(Phase::Playing) => {
+                let round = game.round.as_ref().filter(|r| r.summary.is_none())?;
+
+                match round.dragon_gift {
+                    Some(player) => Some(Pending::DragonGift { player }),
+                    None => Some(Pending::Turn {
+                        player: round.current_player,
+                    }),
+                }
+            }
+            None => None,
+        }
+    }
+
+    /// Whether one of the players the table waits for is away.
+    fn waits_on_away(&self, game: &Game) -> bool {
+        match self {
+            Pending::GrandTichu => game.players.values().any(|p| p.away && p.undealt.is_some()),
+            Pending::Exchange => game
+                .players
+                .values()
+                .any(|p| p.away && p.exchange.is_none()),
+            Pending::Turn { player } | Pending::DragonGift { player } => {
+                game.players.get(player).is_some_and(|p| p.away)
+            }
+        }
+    }
+
+    fn timeout(&self, game: &Game) -> u64 {
+        match self {
+            Pending::GrandTichu => game.config.timeouts.grand_tichu,
+            Pending::Exchange => game.config.timeouts.exchange,
+            Pending::Turn { .. } | Pending::DragonGift { .. } => game.config.timeouts.turn,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Countdown {
+    #[serde(flatten)]
+    pending: Pending,
+    remaining: u64,
+}
+
+/// Counts down the time left for the pending action of a game, one tick per second.
+#[derive(Debug, Default)]
+pub struct Timer {
+    //the same player can be on turn twice in a row, the cards left tell those turns apart
+    running: Option<(Pending, usize)>,
+    remaining: u64,
+}
+
+impl Timer {
+    pub fn tick(&mut self, game: &mut Game, io: &SocketIo) {
+        let cards_left = game
+            .players
+            .values()
+            .filter_map(|p| p.hand.as_ref())
+            .map(|h| h.cards.len())
+            .sum();
+        let running = Pending::of(game).map(|pending| match pending {
+            //the hands grow while players decide, but the phase runs on one clock
+            Pending::GrandTichu | Pending::Exchange => (pending, 0),
+            Pending::Turn { .. } | Pending::DragonGift { .. } => (pending, cards_left),
+        });
+
+        if running != self.running {
+            self.running = running;
+            self.remaining = running.map_or(0, |(pending, _)| pending.timeout(game));
+
+            //a limit of zero never runs out
+            if self.remaining == 0 {
+                return;
+            }
+        }
+
+        let pending = match self.running {
+            Some((pending, _)) if pending.timeout(game) > 0 => pending,
+            _ => return,
+        };
+
+        //the clock stands still until everyone it waits for is back
+        if pending.waits_on_away(game) {
+            return;
+        }
+
+        if self.remaining > 0 {
+            io.within(game.game_id.clone())
+                .emit(
+                    "countdown",
+                    Countdown {
+                        pending,
+                        remaining: self.remaining,
+                    },
+                )
+                .ok();
+            self.remaining -= 1;
+            return;
+        }
+
+        info!("time is up in game {} for {:?}", game.game_id, pending);
+        io.within(game.game_id.clone())
+            .emit("timed-out", pending)
+            .ok();
+
+        //starts over on the next tick, even if the action could not be taken
+        self.running = None;
+
+        if let Err(err) = expire(game, io, pending) {
+            info!("failed taking the action for {:?}: {}", pending, err);
+        }
+    }
+}
+
+fn expire(game: &mut Game, io: &SocketIo, pending: Pending) -> GameResult<()> {
+    match pending {
+        Pending::GrandTichu => {
+            let undecided = game.decline_grand_tichu()?;
+            let count = undecided.len();
+
+            for (i, player_id) in undecided.into_iter().enumerate() {
+                grand_tichu_decided(io, game, player_id, false, i + 1 == count)?;
+            }
+        }
+        Pending::Exchange => {
+            let pending = game.auto_exchange()?;
+            let count = pending.len();
+
+            for (i, player_id) in pending.into_iter().enumerate() {
+                exchange_submitted(io, game, player_id, i + 1 == count)?;
+            }
+        }
+        Pending::Turn { .. } => {
+            let (turn, trick_over) = game.auto_turn()?;
+
+            match turn.action {
+                Action::Play => trick_played(io, game, &turn, trick_over)?,
+                Action::Pass => turn_passed(io, game, turn.player, trick_over)?,
+            }
+        }
+        Pending::DragonGift { .. } => {
+            let (player_id, recipient) = game.auto_gift_dragon()?;
+            dragon_gifted(io, game, player_id, recipient)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use socketioxide::{socket::Sid, SocketIo};
+
+    use crate::game_core::core::{Game, GameConfig, Phase};
+
+    use super::Timer;
+
+    fn seated_game(config: GameConfig) -> Game {
+        let mut game = Game::new("test_game".to_string(), HashMap::new());
+        game.configure(config).unwrap();
+        for i in 0..4 {
+            game.join_lobby(i.to_string(), Sid::new()).unwrap();
+        }
+        game.begin_round().unwrap();
+        game
+    }
+
+    #[test]
+    fn test_timer_waits_while_away() {
+        let (_, io) = SocketIo::new_layer();
+        io.ns("/", || {});
+
+        let mut config = GameConfig {
+            grand_tichu: false,
+            exchange: false,
+            ..Default::default()
+        };
+        config.timeouts.turn = 1;
+        let mut game = seated_game(config);
+
+        let on_turn = game.round.as_ref().unwrap().current_player;
+        game.players.get_mut(&on_turn).unwrap().away = true;
+
+        let mut timer = Timer::default();
+        for _ in 0..5 {
+            timer.tick(&mut game, &io);
+        }
+
+        //nobody played for the player who is away
+        assert_eq!(game.round.as_ref().unwrap().current_player, on_turn);
+        assert_eq!(
+            game.players[&on_turn].hand.as_ref().unwrap().cards.len(),
+            14
+        );
+
+        game.players.get_mut(&on_turn).unwrap().away = false;
+        timer.tick(&mut game, &io);
+
+        assert_eq!(game.round.as_ref().unwrap().current_player, on_turn);
+        timer.tick(&mut game, &io);
+
+        assert_ne!(game.round.as_ref().unwrap().current_player, on_turn);
+    }
+
+    #[test]
+    fn test_timer_runs_per_phase() {
+        let (_, io) = SocketIo::new_layer();
+        io.ns("/", || {});
+
+        let mut config = GameConfig::default();
+        config.timeouts.grand_tichu = 3;
+        let mut game = seated_game(config);
+        assert!(matches!(game.phase, Some(Phase::GrandTichu)));
+
+        let mut timer = Timer::default();
+        timer.tick(&mut game, &io);
+
+        //deciding deals the remaining cards, which does not restart the clock
+        let decided = game.seats()[0].id;
+        game.call_grand_tichu(decided, false).unwrap();
+
+        timer.tick(&mut game, &io);
+        timer.tick(&mut game, &io);
+        let undecided = game.players.values().filter(|p| p.undealt.is_some());
+        assert_eq!(undecided.count(), 3);
+
+        timer.tick(&mut game, &io);
+        assert!(game.players.values().all(|p| p.undealt.is_none()));
+    }
+}
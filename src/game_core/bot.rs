@@ -0,0 +1,283 @@
+use std::collections::HashMap;
+
+use anyhow::anyhow;
+
+use crate::game_core::{
+    core::{
+        fulfils_wish, Action, BotKind, Cards, Exchange, Game, GameResult, Hand, Phase, PlayerId,
+        TrickType, Turn,
+    },
+    moves::legal_plays,
+};
+
+/// Decides for a bot whenever the game waits on it.
+pub trait Strategy: Send + Sync {
+    /// Called with the first eight cards.
+    fn grand_tichu(&self, game: &Game, player_id: PlayerId) -> bool;
+    /// Called with all fourteen cards, as long as the bot has not played yet.
+    fn tichu(&self, game: &Game, player_id: PlayerId) -> bool;
+    /// The card for every other player, keyed by their id.
+    fn exchange(&self, game: &Game, player_id: PlayerId) -> HashMap<PlayerId, Cards>;
+    fn play(&self, game: &Game, player_id: PlayerId) -> Turn;
+    fn gift_dragon(&self, game: &Game, player_id: PlayerId) -> PlayerId;
+}
+
+impl BotKind {
+    pub fn strategy(&self) -> &'static dyn Strategy {
+        match self {
+            BotKind::Rules => &RuleBot,
+        }
+    }
+}
+
+/// Everything a bot can do, as decided by its strategy.
+#[derive(Debug, Clone)]
+pub enum BotAction {
+    GrandTichu {
+        player: PlayerId,
+        call: bool,
+    },
+    Tichu(PlayerId),
+    Exchange(Exchange),
+    Turn(Turn),
+    GiftDragon {
+        player: PlayerId,
+        recipient: PlayerId,
+    },
+}
+
+impl BotAction {
+    /// Returns what the underlying game action returns: whether everyone decided or exchanged,
+    /// or whether the trick is over.
+    pub fn apply(&self, game: &mut Game) -> GameResult<bool> {
+        match self {
+            BotAction::GrandTichu { player, call } => game.call_grand_tichu(*player, *call),
+            BotAction::Tichu(player) => game.call_tichu(*player).map(|_| false),
+            BotAction::Exchange(exchange) => game.submit_exchange(exchange.clone()),
+            BotAction::Turn(turn) => game.play_turn(turn.clone()),
+            BotAction::GiftDragon { player, recipient } => game.gift_dragon(*player, *recipient),
+        }
+    }
+}
+
+//no round takes anywhere near as many actions, unless the bots are stuck
+const MAX_ACTIONS_PER_ROUND: usize = 1000;
+
+/// Lets the bots act until the game waits on a human or the round is over, moving on to the
+/// next phase once every bot decided or exchanged.
+pub fn drive(game: &mut Game) -> GameResult<()> {
+    for _ in 0..MAX_ACTIONS_PER_ROUND {
+        let action = match next_action(game) {
+            Some(action) => action,
+            None => return Ok(()),
+        };
+
+        let done = action.apply(game)?;
+
+        match action {
+            BotAction::GrandTichu { .. } if done => {
+                game.end_grand_tichu()?;
+            }
+            BotAction::Exchange(_) if done => {
+                game.finish_exchange()?;
+            }
+            _ => {}
+        }
+    }
+
+    Err(anyhow!("bots got stuck in {}", game.game_id).into())
+}
+
+/// The next action of a bot the game is waiting on, if any.
+pub fn next_action(game: &Game) -> Option<BotAction> {
+    let bots = game
+        .players
+        .values()
+        .filter_map(|p| p.bot.map(|kind| (p, kind.strategy())));
+
+    match game.phase {
+        Some(Phase::GrandTichu) => bots
+            .filter(|(p, _)| p.undealt.is_some())
+            .map(|(p, strategy)| BotAction::GrandTichu {
+                player: p.id,
+                call: strategy.grand_tichu(game, p.id),
+            })
+            .next(),
+        Some(Phase::Exchanging) => bots
+            .filter(|(p, _)| p.exchange.is_none())
+            .map(|(p, strategy)| {
+                if wants_tichu(game, p.id, strategy) {
+                    return BotAction::Tichu(p.id);
+                }
+
+                BotAction::Exchange(Exchange {
+                    player: p.id,
+                    player_card: strategy.exchange(game, p.id),
+                })
+            })
+            .next(),
+        Some(Phase::Playing) => {
+            let round = game.round.as_ref().filter(|r| r.summary.is_none())?;
+            let player_id = round.dragon_gift.unwrap_or(round.current_player);
+            let strategy = game.players.get(&player_id)?.bot?.strategy();
+
+            if round.dragon_gift.is_some() {
+                return Some(BotAction::GiftDragon {
+                    player: player_id,
+                    recipient: strategy.gift_dragon(game, player_id),
+                });
+            }
+
+            if wants_tichu(game, player_id, strategy) {
+                return Some(BotAction::Tichu(player_id));
+            }
+
+            Some(BotAction::Turn(strategy.play(game, player_id)))
+        }
+        None => None,
+    }
+}
+
+fn wants_tichu(game: &Game, player_id: PlayerId, strategy: &dyn Strategy) -> bool {
+    let untouched = game
+        .players
+        .get(&player_id)
+        .is_some_and(|p| p.call.is_none() && p.hand.as_ref().is_some_and(|h| h.cards.len() == 14));
+
+    untouched && strategy.tichu(game, player_id)
+}
+
+/// Plays its lowest cards, never overplays its partner and only calls with a strong hand.
+pub struct RuleBot;
+
+impl RuleBot {
+    fn strong_cards(hand: &Hand) -> usize {
+        hand.cards
+            .iter()
+            .filter(|c| matches!(c, Cards::Dragon | Cards::Phoenix(_) | Cards::Ace(_)))
+            .count()
+    }
+}
+
+impl Strategy for RuleBot {
+    fn grand_tichu(&self, game: &Game, player_id: PlayerId) -> bool {
+        hand_of(game, player_id).is_some_and(|hand| Self::strong_cards(hand) >= 4)
+    }
+
+    fn tichu(&self, game: &Game, player_id: PlayerId) -> bool {
+        hand_of(game, player_id).is_some_and(|hand| Self::strong_cards(hand) >= 5)
+    }
+
+    fn exchange(&self, game: &Game, player_id: PlayerId) -> HashMap<PlayerId, Cards> {
+        let mut cards = hand_of(game, player_id)
+            .map(|hand| hand.cards.clone())
+            .unwrap_or_default();
+        cards.sort();
+
+        let partner = game.partner(player_id).ok();
+
+        let (partners, opponents): (Vec<_>, Vec<_>) = game
+            .seats()
+            .into_iter()
+            .filter(|p| p.id != player_id)
+            .partition(|p| Some(p.id) == partner);
+
+        //the partner gets the highest card, the opponents the lowest ones
+        let highest = cards.pop();
+        let mut player_card = opponents
+            .into_iter()
+            .map(|p| p.id)
+            .zip(cards)
+            .collect::<HashMap<_, _>>();
+
+        if let (Some(partner), Some(highest)) = (partners.first(), highest) {
+            player_card.insert(partner.id, highest);
+        }
+
+        player_card
+    }
+
+    fn play(&self, game: &Game, player_id: PlayerId) -> Turn {
+        let pass = Turn {
+            player: player_id,
+            action: Action::Pass,
+            cards: None,
+        };
+
+        let (round, hand) = match (game.round.as_ref(), hand_of(game, player_id)) {
+            (Some(round), Some(hand)) => (round, hand),
+            _ => return pass,
+        };
+
+        let last_trick = round.current_trick.last().map(|t| t.as_slice());
+        let plays = legal_plays(hand, last_trick, round.wish);
+        let play = |cards: Vec<Cards>| Turn {
+            player: player_id,
+            action: Action::Play,
+            cards: Some(cards),
+        };
+
+        //while the wish can be fulfilled, every legal play fulfils it
+        if round
+            .wish
+            .is_some_and(|wish| plays.iter().any(|cards| fulfils_wish(cards, wish)))
+        {
+            return plays
+                .into_iter()
+                .min_by_key(|cards| (is_bomb(cards), cards.iter().max().cloned()))
+                .map(play)
+                .unwrap_or(pass);
+        }
+
+        if last_trick.is_none() {
+            return play(lead(game, player_id, hand, plays));
+        }
+
+        if game.partner(player_id).ok() == Some(round.last_played_player) {
+            return pass;
+        }
+
+        plays
+            .into_iter()
+            .filter(|cards| ordinary(cards))
+            .min_by_key(|cards| cards.iter().max().cloned())
+            .map(play)
+            .unwrap_or(pass)
+    }
+
+    fn gift_dragon(&self, game: &Game, player_id: PlayerId) -> PlayerId {
+        game.dragon_recipient(player_id).unwrap_or(player_id)
+    }
+}
+
+fn hand_of(game: &Game, player_id: PlayerId) -> Option<&Hand> {
+    game.players.get(&player_id).and_then(|p| p.hand.as_ref())
+}
+
+/// Leads with the dog while the partner still plays, otherwise with the lowest cards of a rank.
+fn lead(game: &Game, player_id: PlayerId, hand: &Hand, plays: Vec<Vec<Cards>>) -> Vec<Cards> {
+    let partner_plays = game
+        .partner(player_id)
+        .ok()
+        .and_then(|partner| hand_of(game, partner))
+        .is_some();
+
+    if partner_plays && hand.cards.contains(&Cards::Dog) {
+        return vec![Cards::Dog];
+    }
+
+    plays
+        .into_iter()
+        .filter(|cards| ordinary(cards) && cards[0] != Cards::Dog)
+        .min_by_key(|cards| (cards.iter().max().cloned(), std::cmp::Reverse(cards.len())))
+        .unwrap_or_else(|| hand.cards.iter().take(1).cloned().collect())
+}
+
+fn is_bomb(cards: &[Cards]) -> bool {
+    TrickType::try_from(cards).is_ok_and(|trick_type| trick_type.is_bomb())
+}
+
+/// The bot keeps its bombs, and the phoenix is only played alone.
+fn ordinary(cards: &[Cards]) -> bool {
+    !is_bomb(cards) && (cards.len() == 1 || !cards.iter().any(|c| matches!(c, Cards::Phoenix(_))))
+}
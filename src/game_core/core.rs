@@ -1,42 +1,284 @@
-use anyhow::anyhow;
-use std::{
-    collections::HashMap,
-    sync::{Arc, Mutex},
-};
+use std::collections::{BTreeMap, HashMap};
 
 use anyhow::Context;
 
-use rand::Rng;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha20Rng;
 use serde::{Deserialize, Serialize};
 use socketioxide::socket::Sid;
 use tracing::info;
+use uuid::Uuid;
 
-pub(crate) use crate::game_core::types::*;
+pub use crate::game_core::error::{GameError, GameResult};
+use crate::game_core::log::Event;
+use crate::game_core::moves::legal_plays;
+pub use crate::game_core::types::*;
 
 use super::types;
 
-pub type GameStore = Arc<Mutex<HashMap<String, Game>>>;
-
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct Game {
     pub game_id: String,
-    pub players: HashMap<Sid, Player>,
+    pub players: HashMap<PlayerId, Player>,
     pub phase: Option<Phase>,
     pub score_t1: i16,
     pub score_t2: i16,
     pub round: Option<Round>,
+    pub winner: Option<Team>,
+    pub config: GameConfig,
+    /// Every deal of the game is drawn from this seed.
+    pub seed: u64,
+    pub deals: u32,
+    /// Only ever appended to, replaying it rebuilds the game.
+    pub log: Vec<Event>,
 }
 
 impl Game {
-    pub fn new(game_id: String, players: HashMap<Sid, Player>) -> Self {
+    pub fn new(game_id: String, players: HashMap<PlayerId, Player>) -> Self {
         Game {
             game_id,
             players,
+            seed: rand::random(),
+            ..Default::default()
+        }
+    }
+
+    /// Applies the settings of the host. A seed in the config replaces the random one.
+    pub fn configure(&mut self, config: GameConfig) -> GameResult<()> {
+        config.validate()?;
+
+        if let Some(seed) = config.seed {
+            self.seed = seed;
+        }
+        self.config = config;
+        Ok(())
+    }
+
+    /// The players in the order of their seats.
+    pub fn seats(&self) -> Vec<&Player> {
+        let mut seats = self.players.values().collect::<Vec<_>>();
+        seats.sort_by_key(|p| (p.place, p.id));
+        seats
+    }
+
+    /// The final result, once a team has won.
+    pub fn summary(&self) -> Option<GameSummary> {
+        self.winner.clone().map(|winner| GameSummary {
+            winner,
+            score_t1: self.score_t1,
+            score_t2: self.score_t2,
+            seed: self.seed,
+        })
+    }
+
+    /// Finds the player currently connected with the given socket.
+    pub fn player_id(&self, socket_id: Sid) -> GameResult<PlayerId> {
+        self.players
+            .values()
+            .find(|p| p.socket_id == socket_id)
+            .map(|p| p.id)
+            .ok_or(GameError::PlayerNotFound)
+    }
+
+    pub fn player_by_token(&self, token: Uuid) -> GameResult<PlayerId> {
+        self.players
+            .values()
+            .find(|p| p.token == token)
+            .map(|p| p.id)
+            .ok_or(GameError::InvalidToken)
+    }
+
+    /// Binds the seat belonging to the session token to a new socket.
+    pub fn rejoin(&mut self, token: Uuid, socket_id: Sid) -> GameResult<PlayerId> {
+        let player_id = self.player_by_token(token)?;
+        let player = self.players.get_mut(&player_id).unwrap();
+        player.socket_id = socket_id;
+        player.away = false;
+        Ok(player_id)
+    }
+
+    /// Holds the seat of the player connected with the given socket until they rejoin.
+    pub fn mark_away(&mut self, socket_id: Sid) -> GameResult<PlayerId> {
+        let player_id = self.player_id(socket_id)?;
+        self.players.get_mut(&player_id).unwrap().away = true;
+        Ok(player_id)
+    }
+
+    /// Returns true if the player is still away since losing the given socket.
+    pub fn still_away(&self, player_id: PlayerId, socket_id: Sid) -> bool {
+        self.players
+            .get(&player_id)
+            .is_some_and(|p| p.away && p.socket_id == socket_id)
+    }
+
+    /// Returns what the given player is allowed to see of the game.
+    pub fn view_for(&self, player_id: PlayerId) -> GameResult<GameView> {
+        let player = self
+            .players
+            .get(&player_id)
+            .ok_or(GameError::PlayerNotFound)?;
+
+        let mut players = self.players.values().map(|p| p.view()).collect::<Vec<_>>();
+        players.sort_by_key(|p| p.place);
+
+        let round = self.round.as_ref();
+
+        Ok(GameView {
+            game_id: self.game_id.clone(),
+            phase: self.phase.clone(),
+            score_t1: self.score_t1,
+            score_t2: self.score_t2,
+            hand: player.hand.clone(),
+            players,
+            current_player: round.map(|r| r.current_player),
+            trick: round.map(|r| r.current_trick.clone()).unwrap_or_default(),
+            trick_type: round.and_then(|r| r.current_trick_type.clone()),
+            wish: round.and_then(|r| r.wish),
+            dragon_gift: round.and_then(|r| r.dragon_gift),
+            winner: self.winner.clone(),
+            config: self.config.clone(),
+        })
+    }
+
+    /// The team and place the next player joining the lobby gets: the first team with room and
+    /// the lowest place nobody holds.
+    pub fn free_seat(&self) -> GameResult<(Team, u8)> {
+        let seated = self
+            .players
+            .values()
+            .filter(|p| p.team != Some(Team::Spectator))
+            .count();
+
+        if seated >= 4 {
+            return Err(GameError::TeamFull);
+        }
+
+        let team_count = |team: Team| {
+            self.players
+                .values()
+                .filter(|p| p.team == Some(team.clone()))
+                .count()
+        };
+
+        let team = [Team::One, Team::Two]
+            .into_iter()
+            .find(|team| team_count(team.clone()) < 2)
+            .ok_or(GameError::TeamFull)?;
+
+        let place = (1..)
+            .find(|place| self.players.values().all(|p| p.place != *place))
+            .context("failed finding a free place")?;
+
+        Ok((team, place))
+    }
+
+    /// Seats a player joining the lobby on the next free seat.
+    pub fn join_lobby(&mut self, username: String, socket_id: Sid) -> GameResult<Player> {
+        if self.phase.is_some() {
+            return Err(GameError::WrongPhase(
+                "players can only join in the lobby".into(),
+            ));
+        }
+
+        let (team, place) = self.free_seat()?;
+
+        let player = Player {
+            id: PlayerId::new_v4(),
+            socket_id,
+            token: Uuid::new_v4(),
+            username,
+            place,
+            team: Some(team),
+            ..Default::default()
+        };
+
+        self.players.insert(player.id, player.clone());
+        Ok(player)
+    }
+
+    /// Seats a bot in the lobby, on the team that still has a free seat.
+    pub fn add_bot(&mut self, kind: BotKind) -> GameResult<PlayerId> {
+        if self.phase.is_some() {
+            return Err(GameError::WrongPhase(
+                "bots can only join in the lobby".into(),
+            ));
+        }
+
+        let (team, place) = self.free_seat()?;
+
+        let username = (1..)
+            .map(|i| format!("Bot {}", i))
+            .find(|name| self.players.values().all(|p| &p.username != name))
+            .context("failed naming the bot")?;
+
+        let bot = Player {
+            id: PlayerId::new_v4(),
+            username,
+            team: Some(team),
+            place,
+            bot: Some(kind),
             ..Default::default()
+        };
+
+        let id = bot.id;
+        self.players.insert(id, bot);
+        Ok(id)
+    }
+
+    pub fn remove_bot(&mut self, player_id: PlayerId) -> GameResult<()> {
+        if self.phase.is_some() {
+            return Err(GameError::WrongPhase(
+                "bots can only leave in the lobby".into(),
+            ));
         }
+
+        match self.players.get(&player_id) {
+            Some(player) if player.bot.is_some() => {}
+            Some(_) => return Err(GameError::InvalidCall("player is not a bot".into())),
+            None => return Err(GameError::PlayerNotFound),
+        }
+
+        self.players.remove(&player_id);
+        Ok(())
     }
 
-    pub fn join_team(&mut self, player_id: Sid, team: Team) -> anyhow::Result<String> {
+    /// Takes a player out of the lobby. A leaving host hands over to the next human in seat
+    /// order, which is returned. Without a human left there is no host, and the lobby closes.
+    pub fn leave_lobby(&mut self, player_id: PlayerId) -> GameResult<Option<PlayerId>> {
+        if self.phase.is_some() {
+            return Err(GameError::WrongPhase(
+                "players can only leave in the lobby".into(),
+            ));
+        }
+
+        let player = self
+            .players
+            .remove(&player_id)
+            .ok_or(GameError::PlayerNotFound)?;
+
+        if !player.is_host {
+            return Ok(None);
+        }
+
+        let host = self
+            .seats()
+            .into_iter()
+            .find(|p| p.bot.is_none())
+            .map(|p| p.id);
+
+        if let Some(host) = host {
+            self.players.get_mut(&host).unwrap().is_host = true;
+        }
+        Ok(host)
+    }
+
+    pub fn join_team(&mut self, player_id: PlayerId, team: Team) -> GameResult<String> {
+        if self.phase.is_some() {
+            return Err(GameError::WrongPhase(
+                "teams can only be joined in the lobby".into(),
+            ));
+        }
+
         let team_count = self
             .players
             .values()
@@ -44,76 +286,421 @@ impl Game {
             .count();
 
         if team_count >= 2 && team != Team::Spectator {
-            return Err(anyhow!("team is full"));
+            return Err(GameError::TeamFull);
         }
 
         let player = self
             .players
             .get_mut(&player_id)
-            .with_context(|| format!("failed getting player with socket_id {}", player_id))?;
+            .ok_or(GameError::PlayerNotFound)?;
         player.team = Some(team);
         Ok(player.username.clone())
     }
 
+    /// Deals a new round and moves on to its first phase. Without Grand Tichu every player gets
+    /// all their cards right away.
+    pub fn begin_round(&mut self) -> GameResult<Phase> {
+        if self.log.is_empty() {
+            self.log.push(Event::start(self));
+        }
+
+        self.deal_cards();
+
+        if self.config.grand_tichu {
+            self.phase = Some(Phase::GrandTichu);
+            return Ok(Phase::GrandTichu);
+        }
+
+        let ids = self.players.keys().cloned().collect::<Vec<_>>();
+        for id in ids {
+            self.deal_remaining_cards(id)?;
+        }
+
+        self.end_grand_tichu()
+    }
+
+    /// Moves on once every player has all their cards, skipping the exchange if it is not played.
+    pub fn end_grand_tichu(&mut self) -> GameResult<Phase> {
+        if self.config.exchange {
+            self.phase = Some(Phase::Exchanging);
+            return Ok(Phase::Exchanging);
+        }
+
+        self.start_playing()
+    }
+
+    /// Deals the first eight cards to every player. The remaining six are held back until the
+    /// player has decided whether to call Grand Tichu.
     pub fn deal_cards(&mut self) {
-        let hands = generate_hands();
+        //every deal gets its own stream, so the same seed always deals the same rounds. Unlike
+        //StdRng, ChaCha20 is the same on every platform and release, so old logs still replay
+        let mut key = [0u8; 32];
+        key[..8].copy_from_slice(&self.seed.to_le_bytes());
+        key[8..12].copy_from_slice(&self.deals.to_le_bytes());
+        let hands = generate_hands_with(&mut ChaCha20Rng::from_seed(key));
+        self.deals += 1;
+
+        let seats = self.seats().iter().map(|p| p.id).collect::<Vec<_>>();
+
+        let mut dealt = BTreeMap::new();
+
+        for (id, mut hand) in seats.into_iter().zip(hands) {
+            if let Some(player) = self.players.get_mut(&id) {
+                dealt.insert(id, hand.cards.clone());
+                player.undealt = Some(hand.cards.split_off(8));
+                player.hand = Some(hand);
+            }
+        }
 
-        for (player, hand) in self.players.iter_mut().zip(hands.iter()) {
-            player.1.hand = Some(hand.clone());
+        self.log.push(Event::Deal { hands: dealt });
+    }
+
+    /// Clears the last round and deals the next one.
+    pub fn next_round(&mut self) -> GameResult<Phase> {
+        if self.winner.is_some() {
+            return Err(GameError::WrongPhase("game is over".into()));
+        }
+
+        if !self.round_over() {
+            return Err(GameError::WrongPhase("round is not over yet".into()));
         }
+
+        self.players.values_mut().for_each(|p| p.reset());
+        self.round = None;
+        self.begin_round()
+    }
+
+    /// Returns true once the current round has been scored.
+    pub fn round_over(&self) -> bool {
+        self.round_summary().is_some()
+    }
+
+    pub fn round_summary(&self) -> Option<&RoundSummary> {
+        self.round.as_ref().and_then(|r| r.summary.as_ref())
+    }
+
+    pub fn deal_remaining_cards(&mut self, player_id: PlayerId) -> GameResult<()> {
+        let player = self
+            .players
+            .get_mut(&player_id)
+            .ok_or(GameError::PlayerNotFound)?;
+
+        let undealt = player
+            .undealt
+            .take()
+            .ok_or_else(|| GameError::InvalidCall("cards already dealt".into()))?;
+
+        player
+            .hand
+            .as_mut()
+            .context("failed getting hand")?
+            .cards
+            .extend(undealt);
+        Ok(())
     }
 
-    pub fn validate_exchange(&self, exchange: &Exchange) -> anyhow::Result<()> {
+    /// Records the Grand Tichu decision of a player and hands out the remaining six cards.
+    /// Returns true once every player has decided.
+    pub fn call_grand_tichu(&mut self, player_id: PlayerId, call: bool) -> GameResult<bool> {
+        if !matches!(self.phase, Some(Phase::GrandTichu)) {
+            return Err(GameError::WrongPhase(
+                "grand tichu can only be called after the first eight cards".into(),
+            ));
+        }
+
+        let player = self
+            .players
+            .get_mut(&player_id)
+            .ok_or(GameError::PlayerNotFound)?;
+
+        if player.undealt.is_none() {
+            return Err(GameError::InvalidCall(
+                "player already decided on grand tichu".into(),
+            ));
+        }
+
+        if call {
+            player.call = Some(Call::GrandTichu);
+        }
+
+        self.deal_remaining_cards(player_id)?;
+        self.log.push(Event::GrandTichu {
+            player: player_id,
+            call,
+        });
+
+        Ok(self.players.values().all(|p| p.undealt.is_none()))
+    }
+
+    /// Declines Grand Tichu for every player who has not decided in time. Returns those players.
+    pub fn decline_grand_tichu(&mut self) -> GameResult<Vec<PlayerId>> {
+        let undecided = self
+            .seats()
+            .into_iter()
+            .filter(|p| p.undealt.is_some())
+            .map(|p| p.id)
+            .collect::<Vec<_>>();
+
+        for player_id in undecided.iter() {
+            self.call_grand_tichu(*player_id, false)?;
+        }
+
+        Ok(undecided)
+    }
+
+    /// Calls Tichu for a player who has all fourteen cards and has not played any of them. During
+    /// Grand Tichu that is a player who already declined it.
+    pub fn call_tichu(&mut self, player_id: PlayerId) -> GameResult<()> {
+        if self.phase.is_none() {
+            return Err(GameError::WrongPhase(
+                "tichu can not be called in this phase".into(),
+            ));
+        }
+
+        let player = self
+            .players
+            .get_mut(&player_id)
+            .ok_or(GameError::PlayerNotFound)?;
+
+        if player.call.is_some() {
+            return Err(GameError::InvalidCall(format!(
+                "player already called {:?}",
+                player.call
+            )));
+        }
+
+        //a tichu can only be called before the first card is played
+        match &player.hand {
+            Some(hand) if hand.cards.len() == 14 => {}
+            _ => {
+                return Err(GameError::InvalidCall(
+                    "tichu can only be called before playing the first card".into(),
+                ))
+            }
+        }
+
+        player.call = Some(Call::Tichu);
+        self.log.push(Event::Tichu { player: player_id });
+        Ok(())
+    }
+
+    pub fn validate_exchange(&self, exchange: &Exchange) -> GameResult<()> {
         let player = self
             .players
             .get(&exchange.player)
-            .context("failed getting player")?;
+            .ok_or(GameError::PlayerNotFound)?;
 
-        if exchange.player_card.contains_key(&player.username) {
+        if exchange.player_card.contains_key(&player.id) {
             info!("cant exchange with yourself");
-            return Err(anyhow!("cant exchange with yourself"));
+            return Err(GameError::InvalidExchange(
+                "cant exchange with yourself".into(),
+            ));
+        }
+
+        if !exchange
+            .player_card
+            .keys()
+            .all(|id| self.players.contains_key(id))
+        {
+            info!("failed to exchange cards, unknown player");
+            return Err(GameError::InvalidExchange(
+                "failed to exchange cards".into(),
+            ));
         }
 
-        let mut unique_cards = exchange.player_card.values().cloned().collect::<Vec<_>>();
-        unique_cards.sort();
-        unique_cards.dedup();
+        //colors are equal when sorting, so the cards are compared one by one
+        let cards = exchange.player_card.values().cloned().collect::<Vec<_>>();
+        let unique = cards
+            .iter()
+            .enumerate()
+            .all(|(i, card)| !cards[i + 1..].contains(card));
 
-        if unique_cards.len() != 3 {
+        if cards.len() != 3 || !unique {
             info!("failed to exchange cards, must be 3 unique cards");
-            return Err(anyhow!("failed to exchange cards"));
+            return Err(GameError::InvalidExchange(
+                "failed to exchange cards".into(),
+            ));
         }
 
         let player_hand = if let Some(hand) = &player.hand {
             hand
         } else {
             info!("failed to exchange cards, player has no hand");
-            return Err(anyhow!("failed to exchange cards"));
+            return Err(GameError::InvalidExchange(
+                "failed to exchange cards".into(),
+            ));
         };
 
-        if !player_owns_cards(player_hand, unique_cards.as_slice()) {
+        if !player_owns_cards(player_hand, cards.as_slice()) {
             info!("failed to exchange cards, player does not own all cards");
-            return Err(anyhow!("failed to exchange cards"));
+            return Err(GameError::InvalidExchange(
+                "failed to exchange cards".into(),
+            ));
         }
 
         Ok(())
     }
 
-    pub fn start(&mut self) -> anyhow::Result<()> {
-        let team_1 = self.players.values().filter(|p| p.team == Some(Team::One));
+    /// Stores the cards a player wants to pass on. Returns true once every player has submitted
+    /// their exchange and the cards can be swapped.
+    pub fn submit_exchange(&mut self, exchange: Exchange) -> GameResult<bool> {
+        if !matches!(self.phase, Some(Phase::Exchanging)) {
+            return Err(GameError::WrongPhase("not in exchange phase".into()));
+        }
+
+        self.validate_exchange(&exchange)?;
+
+        let player = self
+            .players
+            .get_mut(&exchange.player)
+            .ok_or(GameError::PlayerNotFound)?;
+
+        if player.exchange.is_some() {
+            return Err(GameError::InvalidExchange("cards already exchanged".into()));
+        }
+
+        player.exchange = Some(exchange.player_card.clone());
+        self.log.push(Event::Exchange(exchange));
+
+        Ok(self.players.values().all(|p| p.exchange.is_some()))
+    }
+
+    /// Submits the three lowest cards of every player who has not chosen their cards in time,
+    /// one for each other player. Returns those players.
+    pub fn auto_exchange(&mut self) -> GameResult<Vec<PlayerId>> {
+        let pending = self
+            .seats()
+            .into_iter()
+            .filter(|p| p.exchange.is_none())
+            .map(|p| p.id)
+            .collect::<Vec<_>>();
+
+        for player_id in pending.iter() {
+            let player = self
+                .players
+                .get(player_id)
+                .context("failed getting player")?;
+
+            let mut cards = player
+                .hand
+                .as_ref()
+                .map(|h| h.cards.clone())
+                .context("failed getting hand")?;
+            cards.sort();
+
+            let player_card = self
+                .seats()
+                .into_iter()
+                .filter(|p| p.id != *player_id)
+                .map(|p| p.id)
+                .zip(cards)
+                .collect::<HashMap<_, _>>();
+
+            self.submit_exchange(Exchange {
+                player: *player_id,
+                player_card,
+            })?;
+        }
+
+        Ok(pending)
+    }
+
+    /// Swaps the submitted cards between all hands. Nothing is moved unless every exchange is
+    /// present. Returns the received cards per player, keyed by the id of the giver.
+    pub fn exchange_cards(&mut self) -> GameResult<HashMap<PlayerId, HashMap<PlayerId, Cards>>> {
+        let mut received: HashMap<PlayerId, HashMap<PlayerId, Cards>> = HashMap::new();
+
+        for player in self.players.values() {
+            let exchange = player
+                .exchange
+                .as_ref()
+                .with_context(|| format!("player {} has not exchanged yet", player.username))?;
+
+            if player.hand.is_none() {
+                return Err(GameError::InvalidExchange(format!(
+                    "player {} has no hand",
+                    player.username
+                )));
+            }
+
+            for (receiver, card) in exchange {
+                let receiver = self
+                    .players
+                    .get(receiver)
+                    .with_context(|| format!("failed getting player {}", receiver))?;
+
+                received
+                    .entry(receiver.id)
+                    .or_default()
+                    .insert(player.id, card.clone());
+            }
+        }
+
+        for player in self.players.values_mut() {
+            let given = player
+                .exchange
+                .as_ref()
+                .unwrap()
+                .values()
+                .cloned()
+                .collect::<Vec<_>>();
+
+            player
+                .hand
+                .as_mut()
+                .context("failed getting hand")?
+                .cards
+                .retain(|c| !given.contains(c));
+        }
+
+        for (id, cards) in received.iter() {
+            //sorted by giver, so the hands do not depend on the order of the map
+            let mut cards = cards.iter().collect::<Vec<_>>();
+            cards.sort_by_key(|(giver, _)| *giver);
+
+            self.players
+                .get_mut(id)
+                .and_then(|p| p.hand.as_mut())
+                .context("failed getting hand")?
+                .cards
+                .extend(cards.into_iter().map(|(_, card)| card.clone()));
+        }
+
+        Ok(received)
+    }
+
+    /// Swaps the cards once everyone chose theirs and starts playing.
+    pub fn finish_exchange(&mut self) -> GameResult<HashMap<PlayerId, HashMap<PlayerId, Cards>>> {
+        let received = self.exchange_cards()?;
+        self.start_playing()?;
+        Ok(received)
+    }
+
+    /// Hands the lead to the player with the mahjong and moves on to playing.
+    pub fn start_playing(&mut self) -> GameResult<Phase> {
+        self.start()?;
+        self.phase = Some(Phase::Playing);
+        Ok(Phase::Playing)
+    }
+
+    pub fn start(&mut self) -> GameResult<()> {
+        let seats = self.seats();
+
+        let team_1 = seats.iter().filter(|p| p.team == Some(Team::One));
 
-        let team_2 = self.players.values().filter(|p| p.team == Some(Team::Two));
+        let team_2 = seats.iter().filter(|p| p.team == Some(Team::Two));
 
         let turns = team_1
             .zip(team_2)
             .flat_map(|(p1, p2)| vec![p1, p2])
-            .cloned()
+            .map(|p| (*p).clone())
             .collect::<Vec<_>>();
 
-        let player_turn_sequence = types::generate_player_turn_sequence(turns);
+        let player_turn_sequence = types::generate_player_turn_sequence(turns)?;
 
         let round = Round {
             prev_next_player: player_turn_sequence,
-            current_player: Sid::new(),
+            current_player: PlayerId::new_v4(),
             ..Default::default()
         };
 
@@ -141,27 +728,95 @@ impl Game {
         Ok(())
     }
 
-    pub fn play_turn(&mut self, turn: Turn) -> anyhow::Result<bool> {
-        let current_player = self
+    pub fn play_turn(&mut self, turn: Turn) -> GameResult<bool> {
+        self.logged(Event::Turn(turn.clone()), |game| game.take_turn(turn))
+    }
+
+    /// Logs an action ahead of everything following from it, and drops it again if it fails.
+    fn logged<T>(
+        &mut self,
+        event: Event,
+        action: impl FnOnce(&mut Self) -> GameResult<T>,
+    ) -> GameResult<T> {
+        let len = self.log.len();
+        self.log.push(event);
+
+        let result = action(self);
+        if result.is_err() {
+            self.log.truncate(len);
+        }
+        result
+    }
+
+    fn take_turn(&mut self, turn: Turn) -> GameResult<bool> {
+        let round = self
             .round
             .as_ref()
-            .context("failed getting player turn iterator")?
-            .current_player;
+            .ok_or_else(|| GameError::WrongPhase("round has not started".into()))?;
 
-        if current_player != turn.player {
-            return Err(anyhow!("not your turn"));
+        if round.summary.is_some() {
+            return Err(GameError::WrongPhase("round is over".into()));
+        }
+
+        if round.dragon_gift.is_some() {
+            return Err(GameError::DragonNotGifted);
+        }
+
+        let current_player = round.current_player;
+
+        //bombs can be played at any time, interrupting the turn order
+        let is_bomb = match (&turn.action, &turn.cards) {
+            (Action::Play, Some(cards)) => TrickType::try_from(cards.as_slice())
+                .map(|t| t.is_bomb())
+                .unwrap_or(false),
+            _ => false,
+        };
+
+        let may_interrupt =
+            is_bomb && !round.current_trick.is_empty() && self.config.house_rules.bombs_out_of_turn;
+
+        if current_player != turn.player && !may_interrupt {
+            return Err(GameError::NotYourTurn);
         }
 
         let player = self
             .players
             .get_mut(&turn.player)
-            .with_context(|| format!("failed getting player with socket_id {}", turn.player))?;
+            .ok_or(GameError::PlayerNotFound)?;
+
+        if player.hand.is_none() {
+            return Err(GameError::InvalidTrick("player has no cards left".into()));
+        }
 
         let round = self.round.as_mut().context("failed getting round")?;
 
+        if current_player != turn.player {
+            info!("player {} interrupts with a bomb", player.username);
+        } else if let Some(wish) = round.wish {
+            let hand = player.hand.as_ref().context("player has no cards left")?;
+            let last_trick = round.current_trick.last().map(|t| t.as_slice());
+
+            let fulfilled = match (&turn.action, &turn.cards) {
+                (Action::Play, Some(cards)) => fulfils_wish(cards, wish),
+                _ => false,
+            };
+
+            let can_fulfil = legal_plays(hand, last_trick, Some(wish))
+                .iter()
+                .any(|cards| fulfils_wish(cards, wish));
+
+            if !fulfilled && can_fulfil {
+                return Err(GameError::WishNotFulfilled);
+            }
+        }
+
         if round.current_trick.is_empty() {
-            self.init_round(turn)?;
-            return Ok(false);
+            if let Action::Pass = turn.action {
+                return Err(GameError::InvalidTrick(
+                    "the trick leader can not pass".into(),
+                ));
+            }
+            return self.init_round(turn);
         }
 
         if let Action::Pass = turn.action {
@@ -170,10 +825,15 @@ impl Game {
             match round.next() {
                 Some(_) => return Ok(false),
                 None => {
-                    if self.players.values().filter(|p| p.hand.is_some()).count() == 1 {
-                        //TODO: handle game end
-                        self.cleanup_round()?;
-                        return Ok(true);
+                    //everyone passed, the trick goes to the last player who played
+                    self.cleanup_trick()?;
+
+                    let round = self.round.as_mut().unwrap();
+                    if round.dragon_gift.is_none() {
+                        let winner = round.last_played_player;
+                        round
+                            .jump_to(winner)
+                            .context("failed handing the lead to the trick winner")?;
                     }
                     return Ok(true);
                 }
@@ -181,20 +841,27 @@ impl Game {
         }
 
         if Action::Play != turn.action {
-            return Err(anyhow!("invalid action"));
+            return Err(GameError::InvalidTrick("invalid action".into()));
         }
 
         let trick = if let Some(cards) = &turn.cards {
             cards.as_slice()
         } else {
-            return Err(anyhow!("no cards played"));
+            return Err(GameError::InvalidTrick("no cards played".into()));
         };
 
         if !player_owns_cards(player.hand.as_ref().unwrap(), trick) {
-            return Err(anyhow!("player does not own all cards"));
+            return Err(GameError::InvalidTrick(
+                "player does not own all cards".into(),
+            ));
         }
 
-        compare_tricks(round.current_trick.last().unwrap(), trick)?;
+        let last_trick = round.current_trick.last().unwrap();
+        let resolved_trick = resolve_phoenix(trick, Some(last_trick))?;
+        let trick = resolved_trick.as_slice();
+
+        compare_tricks(last_trick, trick)?;
+        let wish = wished_rank(trick)?;
 
         player
             .hand
@@ -203,19 +870,21 @@ impl Game {
             .cards
             .retain(|c| !trick.contains(c));
 
-        if player.hand.as_ref().unwrap().cards.is_empty() {
-            player.hand = None;
-
-            if round.first_to_finish.is_none() {
-                round.first_to_finish = Some(player.socket_id);
-            }
-        }
+        let finished = player.hand.as_ref().unwrap().cards.is_empty();
 
         round.current_trick.push(trick.to_vec());
         round.current_trick_type = Some(TrickType::try_from(trick)?);
+        round.update_wish(trick, wish);
 
-        self.round.as_mut().unwrap().last_played_player = player.socket_id;
-        self.round.as_mut().unwrap().previous_action = Some(Action::Play);
+        //after a bomb the turn continues with the player after the bomber
+        round.current_player = player.id;
+        round.last_played_player = player.id;
+        round.previous_action = Some(Action::Play);
+
+        if finished && self.player_finished(turn.player)? {
+            self.finish_round()?;
+            return Ok(true);
+        }
 
         self.round
             .as_mut()
@@ -226,7 +895,51 @@ impl Game {
         Ok(false)
     }
 
-    fn init_round(&mut self, turn: Turn) -> anyhow::Result<()> {
+    /// Takes the turn of the current player once their time is up. They pass, or play their
+    /// lowest single when leading. A pending wish is fulfilled whenever the hand allows it.
+    /// Returns the turn that was taken and whether it ended the trick.
+    pub fn auto_turn(&mut self) -> GameResult<(Turn, bool)> {
+        let round = self
+            .round
+            .as_ref()
+            .ok_or_else(|| GameError::WrongPhase("round has not started".into()))?;
+
+        let player = round.current_player;
+        let hand = self
+            .players
+            .get(&player)
+            .and_then(|p| p.hand.as_ref())
+            .context("failed getting hand of current player")?;
+        let last_trick = round.current_trick.last().map(|t| t.as_slice());
+
+        let wished = round.wish.and_then(|wish| {
+            legal_plays(hand, last_trick, Some(wish))
+                .into_iter()
+                .filter(|cards| fulfils_wish(cards, wish))
+                .min_by_key(|cards| cards.len())
+        });
+
+        let cards = match (wished, last_trick) {
+            (Some(cards), _) => Some(cards),
+            (None, None) => hand.cards.iter().min().map(|card| vec![card.clone()]),
+            (None, Some(_)) => None,
+        };
+
+        let turn = Turn {
+            player,
+            action: if cards.is_some() {
+                Action::Play
+            } else {
+                Action::Pass
+            },
+            cards,
+        };
+
+        let trick_over = self.play_turn(turn.clone())?;
+        Ok((turn, trick_over))
+    }
+
+    fn init_round(&mut self, turn: Turn) -> GameResult<bool> {
         let current_player = self
             .round
             .as_ref()
@@ -236,32 +949,39 @@ impl Game {
         let round = self.round.as_mut().context("failed getting round")?;
 
         if current_player != turn.player {
-            return Err(anyhow!("not your turn"));
+            return Err(GameError::NotYourTurn);
         }
 
         if Action::Play != turn.action {
-            return Err(anyhow!("invalid action"));
+            return Err(GameError::InvalidTrick("invalid action".into()));
         }
 
         if !round.current_trick.is_empty() {
-            return Err(anyhow!("trick already started"));
+            return Err(GameError::InvalidTrick("trick already started".into()));
         }
 
         let player = self
             .players
             .get_mut(&turn.player)
-            .with_context(|| format!("failed getting player with socket_id {}", turn.player))?;
+            .ok_or(GameError::PlayerNotFound)?;
 
         let trick = if let Some(cards) = &turn.cards {
             cards.as_slice()
         } else {
-            return Err(anyhow!("no cards played"));
+            return Err(GameError::InvalidTrick("no cards played".into()));
         };
 
         if !player_owns_cards(player.hand.as_ref().unwrap(), trick) {
-            return Err(anyhow!("player does not own all cards"));
+            return Err(GameError::InvalidTrick(
+                "player does not own all cards".into(),
+            ));
         }
 
+        let resolved_trick = resolve_phoenix(trick, None)?;
+        let trick = resolved_trick.as_slice();
+        let trick_type = TrickType::try_from(trick)?;
+        let wish = wished_rank(trick)?;
+
         player
             .hand
             .as_mut()
@@ -269,22 +989,115 @@ impl Game {
             .cards
             .retain(|c| !trick.contains(c));
 
-        round.current_trick_type = Some(TrickType::try_from(trick)?);
+        let finished = player.hand.as_ref().unwrap().cards.is_empty();
+        round.trick_won = None;
+
+        //the dog leaves nothing on the table and hands the lead to the partner
+        if trick == [Cards::Dog] {
+            if finished && self.player_finished(turn.player)? {
+                self.cleanup_round()?;
+                return Ok(true);
+            }
+
+            let partner = self.partner(turn.player)?;
+            self.round
+                .as_mut()
+                .unwrap()
+                .jump_to(partner)
+                .context("failed handing the lead to the partner")?;
+
+            return Ok(false);
+        }
+
+        round.current_trick_type = Some(trick_type);
         round.current_trick.push(trick.to_vec());
-        round.last_played_player = player.socket_id;
+        round.update_wish(trick, wish);
+        round.last_played_player = player.id;
         round.previous_action = Some(Action::Play);
-        round.next().context("failed getting next player")?;
 
-        Ok(())
+        if finished && self.player_finished(turn.player)? {
+            self.finish_round()?;
+            return Ok(true);
+        }
+
+        self.round
+            .as_mut()
+            .unwrap()
+            .next()
+            .context("failed getting next player")?;
+
+        Ok(false)
+    }
+
+    pub fn partner(&self, player_id: PlayerId) -> GameResult<PlayerId> {
+        let team = self
+            .players
+            .get(&player_id)
+            .and_then(|p| p.team.clone())
+            .context("failed getting team")?;
+
+        let partner = self
+            .players
+            .values()
+            .find(|p| p.id != player_id && p.team == Some(team.clone()))
+            .map(|p| p.id)
+            .context("failed getting partner")?;
+
+        Ok(partner)
     }
 
-    pub fn cleanup_trick(&mut self) -> anyhow::Result<()> {
+    /// Takes the empty hand away from a player who played their last card. Returns true if the
+    /// round is over, either because only one player has cards left or because the partner of
+    /// the player finished first, which is a double victory.
+    fn player_finished(&mut self, player_id: PlayerId) -> GameResult<bool> {
+        let player = self
+            .players
+            .get_mut(&player_id)
+            .ok_or(GameError::PlayerNotFound)?;
+
+        player.hand = None;
+        let team = player.team.clone();
+
+        let round = self.round.as_mut().context("failed getting round")?;
+        round.mark_finished(player_id);
+
+        let finished_players = self.players.values().filter(|p| p.hand.is_none()).count();
+
+        let first_to_finish = round
+            .first_to_finish
+            .and_then(|first| self.players.get(&first))
+            .context("failed getting first player")?;
+
+        if finished_players == 2
+            && first_to_finish.team == team
+            && self.config.house_rules.double_victory
+        {
+            round.double_victory = team;
+            return Ok(true);
+        }
+
+        Ok(finished_players == 3)
+    }
+
+    pub fn cleanup_trick(&mut self) -> GameResult<()> {
         let round = self.round.as_mut().context("failed getting round")?;
         let trick_winner = round.last_played_player;
+
+        //a trick won with the dragon has to be given to an opponent
+        if round.current_trick.last() == Some(&vec![Cards::Dragon]) {
+            round.dragon_gift = Some(trick_winner);
+            return Ok(());
+        }
+
+        self.collect_trick(trick_winner)
+    }
+
+    fn collect_trick(&mut self, trick_winner: PlayerId) -> GameResult<()> {
+        let round = self.round.as_mut().context("failed getting round")?;
         let winning_player = self
             .players
             .get_mut(&trick_winner)
-            .with_context(|| format!("failed getting player with socket_id {}", trick_winner))?;
+            .ok_or(GameError::PlayerNotFound)?;
 
         let trick_points = round
             .current_trick
@@ -294,12 +1107,188 @@ impl Game {
 
         winning_player.trick_points += trick_points;
 
+        let trick_won = TrickWon {
+            winner: trick_winner,
+            points: trick_points,
+        };
+        round.trick_won = Some(trick_won.clone());
         round.current_trick.clear();
         round.current_trick_type = None;
+        self.log.push(Event::TrickWon(trick_won));
         Ok(())
     }
 
-    pub fn cleanup_round(&mut self) -> anyhow::Result<Option<Team>> {
+    /// Gives the trick won with the dragon to an opponent. Returns true if this was the last
+    /// trick of the round.
+    pub fn gift_dragon(&mut self, player_id: PlayerId, recipient: PlayerId) -> GameResult<bool> {
+        let event = Event::DragonGift {
+            player: player_id,
+            recipient,
+        };
+        self.logged(event, |game| game.give_dragon(player_id, recipient))
+    }
+
+    fn give_dragon(&mut self, player_id: PlayerId, recipient: PlayerId) -> GameResult<bool> {
+        let round = self.round.as_ref().context("failed getting round")?;
+
+        if round.dragon_gift != Some(player_id) {
+            return Err(GameError::InvalidDragonGift(
+                "player has no dragon to give away".into(),
+            ));
+        }
+
+        let team = self
+            .players
+            .get(&player_id)
+            .and_then(|p| p.team.clone())
+            .context("failed getting team")?;
+
+        let recipient_team = self
+            .players
+            .get(&recipient)
+            .and_then(|p| p.team.clone())
+            .ok_or(GameError::PlayerNotFound)?;
+
+        if recipient_team == team || recipient_team == Team::Spectator {
+            return Err(GameError::InvalidDragonGift(
+                "the dragon must be given to an opponent".into(),
+            ));
+        }
+
+        self.collect_trick(recipient)?;
+        self.round.as_mut().unwrap().dragon_gift = None;
+
+        if self.players.values().filter(|p| p.hand.is_some()).count() <= 1 {
+            self.cleanup_round()?;
+            return Ok(true);
+        }
+
+        //the lead stays with the player who won the trick with the dragon
+        self.round
+            .as_mut()
+            .unwrap()
+            .jump_to(player_id)
+            .context("failed handing the lead to the trick winner")?;
+
+        Ok(false)
+    }
+
+    /// Gives a pending dragon trick to an opponent once the winner did not choose in time. Returns the winner and the recipient.
+    pub fn auto_gift_dragon(&mut self) -> GameResult<(PlayerId, PlayerId)> {
+        let player_id = self
+            .round
+            .as_ref()
+            .and_then(|r| r.dragon_gift)
+            .ok_or_else(|| GameError::InvalidDragonGift("no dragon to give away".into()))?;
+
+        let recipient = self.dragon_recipient(player_id)?;
+
+        self.gift_dragon(player_id, recipient)?;
+        Ok((player_id, recipient))
+    }
+
+    /// The opponent with the most cards left, who is the least likely to make use of the points.
+    pub fn dragon_recipient(&self, player_id: PlayerId) -> GameResult<PlayerId> {
+        let team = self
+            .players
+            .get(&player_id)
+            .and_then(|p| p.team.clone())
+            .context("failed getting team")?;
+
+        let recipient = self
+            .seats()
+            .into_iter()
+            .filter(|p| p.team.is_some() && p.team != Some(team.clone()))
+            .filter(|p| p.team != Some(Team::Spectator))
+            .max_by_key(|p| p.hand.as_ref().map_or(0, |h| h.cards.len()))
+            .map(|p| p.id)
+            .context("failed getting an opponent")?;
+
+        Ok(recipient)
+    }
+
+    /// Collects the last trick and scores the round. If the last trick was won with the dragon,
+    /// the round is scored once it has been given away.
+    fn finish_round(&mut self) -> GameResult<()> {
+        let round = self.round.as_ref().context("failed getting round")?;
+
+        //on a double victory the cards are not counted
+        if round.double_victory.is_none() {
+            self.cleanup_trick()?;
+        }
+
+        if self.round.as_ref().unwrap().dragon_gift.is_some() {
+            return Ok(());
+        }
+
+        self.cleanup_round()?;
+        Ok(())
+    }
+
+    /// Scores the round and remembers a summary of it. Returns the winning team once a team
+    /// reached the target score, on a tie the game goes on.
+    pub fn cleanup_round(&mut self) -> GameResult<Option<Team>> {
+        let (previous_t1, previous_t2) = (self.score_t1, self.score_t2);
+
+        let double_victory = self
+            .round
+            .as_ref()
+            .context("failed getting round")?
+            .double_victory
+            .clone();
+
+        match double_victory {
+            //a double victory is worth 200 points and the cards are not counted
+            Some(Team::One) => self.score_t1 += 200,
+            Some(Team::Two) => self.score_t2 += 200,
+            Some(Team::Spectator) => return Err(GameError::InvalidTeams),
+            None => self.count_card_points()?,
+        }
+
+        let round = self.round.as_ref().context("failed getting round")?;
+
+        for player in self.players.values() {
+            let call_points = match &player.call {
+                Some(call) if round.first_to_finish == Some(player.id) => call.get_points(),
+                Some(call) => -call.get_points(),
+                None => continue,
+            };
+
+            match player.team.as_ref().context("failed getting team")? {
+                Team::One => {
+                    self.score_t1 += call_points;
+                }
+                Team::Two => {
+                    self.score_t2 += call_points;
+                }
+                Team::Spectator => return Err(GameError::InvalidTeams),
+            };
+        }
+
+        let summary = RoundSummary {
+            points_t1: self.score_t1 - previous_t1,
+            points_t2: self.score_t2 - previous_t2,
+            score_t1: self.score_t1,
+            score_t2: self.score_t2,
+        };
+        self.round.as_mut().unwrap().summary = Some(summary.clone());
+        self.log.push(Event::RoundScored(summary));
+
+        if self.score_t1.max(self.score_t2) >= self.config.target_score
+            && self.score_t1 != self.score_t2
+        {
+            self.winner = if self.score_t1 > self.score_t2 {
+                Some(Team::One)
+            } else {
+                Some(Team::Two)
+            };
+            self.log.extend(self.summary().map(Event::GameOver));
+        }
+
+        Ok(self.winner.clone())
+    }
+
+    fn count_card_points(&mut self) -> GameResult<()> {
         let last_player_with_cards = self
             .players
             .iter_mut()
@@ -328,7 +1317,7 @@ impl Game {
             Team::Two => {
                 self.score_t1 += points_remaining_cards as i16;
             }
-            Team::Spectator => return Err(anyhow!("invalid team")),
+            Team::Spectator => return Err(GameError::InvalidTeams),
         };
 
         let trick_points_last_player = last_player_with_cards.trick_points;
@@ -352,30 +1341,28 @@ impl Game {
                 Team::Two => {
                     self.score_t2 += player.trick_points as i16;
                 }
-                Team::Spectator => return Err(anyhow!("invalid team")),
+                Team::Spectator => return Err(GameError::InvalidTeams),
             };
         }
 
-        if self.score_t1 >= 1000 {
-            return Ok(Some(Team::One));
-        }
-
-        if self.score_t2 >= 1000 {
-            return Ok(Some(Team::Two));
-        }
-
-        Ok(None)
+        Ok(())
     }
 }
 
-pub fn compare_tricks(last_trick: &[Cards], players_trick: &[Cards]) -> anyhow::Result<()> {
-    let players_trick_type = TrickType::try_from(players_trick).with_context(|| {
-        format!(
+pub fn compare_tricks(last_trick: &[Cards], players_trick: &[Cards]) -> GameResult<()> {
+    let players_trick_type = TrickType::try_from(players_trick).map_err(|_| {
+        GameError::InvalidTrick(format!(
             "failed converting players trick {:?} to trick type",
             players_trick
-        )
+        ))
     })?;
 
+    if players_trick.contains(&Cards::Dog) {
+        return Err(GameError::InvalidTrick(
+            "the dog can only be played as the opening lead".into(),
+        ));
+    }
+
     //this should never fail, since the last trick is already a valid trick
     let last_trick_type = TrickType::try_from(last_trick)?;
 
@@ -384,29 +1371,29 @@ pub fn compare_tricks(last_trick: &[Cards], players_trick: &[Cards]) -> anyhow::
             if let TrickType::Single = players_trick_type {
                 return match players_trick[0].clone() {
                     Cards::Dragon => Ok(()),
+                    //the phoenix beats every single card except the dragon
+                    Cards::Phoenix(_) if last_trick[0] != Cards::Dragon => Ok(()),
                     _ => {
                         match last_trick[0].clone() {
                             Cards::Phoenix(card) => {
-                                //phoenix only counts as 0.5, but i don't want to support floats so if they
-                                //have the same value, the phoenix in theory would be 0.5 lower
-                                if card.value <= players_trick[0].get_card_number() {
+                                //the phoenix counts half a rank above the card it was played on,
+                                //which is the rank it stores
+                                if card.value < players_trick[0].get_card_number() {
                                     return Ok(());
                                 }
-                                Err(anyhow!(
+                                Err(GameError::InvalidTrick(format!(
                                     "trick {:?} is not greater than last trick {:?}",
-                                    players_trick,
-                                    last_trick
-                                ))
+                                    players_trick, last_trick
+                                )))
                             }
                             _ => {
                                 if last_trick < players_trick {
                                     return Ok(());
                                 }
-                                Err(anyhow!(
+                                Err(GameError::InvalidTrick(format!(
                                     "trick {:?} is not greater than last trick {:?}",
-                                    players_trick,
-                                    last_trick
-                                ))
+                                    players_trick, last_trick
+                                )))
                             }
                         }
                     }
@@ -420,11 +1407,10 @@ pub fn compare_tricks(last_trick: &[Cards], players_trick: &[Cards]) -> anyhow::
                 return Ok(());
             }
 
-            Err(anyhow!(
+            Err(GameError::InvalidTrick(format!(
                 "Trick type {:?} does not match {:?}",
-                players_trick_type,
-                last_trick_type
-            ))
+                players_trick_type, last_trick_type
+            )))
         }
         TrickType::Pair => {
             if let TrickType::Pair = players_trick_type {
@@ -432,11 +1418,10 @@ pub fn compare_tricks(last_trick: &[Cards], players_trick: &[Cards]) -> anyhow::
                     return Ok(());
                 }
 
-                return Err(anyhow!(
+                return Err(GameError::InvalidTrick(format!(
                     "tick {:?} is not greater than last trick {:?}",
-                    players_trick,
-                    last_trick
-                ));
+                    players_trick, last_trick
+                )));
             }
 
             if let TrickType::FourOfAKind = players_trick_type {
@@ -446,11 +1431,10 @@ pub fn compare_tricks(last_trick: &[Cards], players_trick: &[Cards]) -> anyhow::
             if let TrickType::StraightFlush = players_trick_type {
                 return Ok(());
             }
-            Err(anyhow!(
+            Err(GameError::InvalidTrick(format!(
                 "Trick type {:?} does not match {:?}",
-                players_trick_type,
-                last_trick_type
-            ))
+                players_trick_type, last_trick_type
+            )))
         }
         TrickType::Triple => {
             if let TrickType::Triple = players_trick_type {
@@ -458,11 +1442,10 @@ pub fn compare_tricks(last_trick: &[Cards], players_trick: &[Cards]) -> anyhow::
                     return Ok(());
                 }
 
-                return Err(anyhow!(
+                return Err(GameError::InvalidTrick(format!(
                     "tick {:?} is not greater than last trick {:?}",
-                    players_trick,
-                    last_trick
-                ));
+                    players_trick, last_trick
+                )));
             }
             if let TrickType::FourOfAKind = players_trick_type {
                 return Ok(());
@@ -471,11 +1454,10 @@ pub fn compare_tricks(last_trick: &[Cards], players_trick: &[Cards]) -> anyhow::
             if let TrickType::StraightFlush = players_trick_type {
                 return Ok(());
             }
-            Err(anyhow!(
+            Err(GameError::InvalidTrick(format!(
                 "Trick type {:?} does not match {:?}",
-                players_trick_type,
-                last_trick_type
-            ))
+                players_trick_type, last_trick_type
+            )))
         }
         TrickType::FullHouse => {
             if let TrickType::FullHouse = players_trick_type {
@@ -511,11 +1493,10 @@ pub fn compare_tricks(last_trick: &[Cards], players_trick: &[Cards]) -> anyhow::
                     return Ok(());
                 }
 
-                return Err(anyhow!(
+                return Err(GameError::InvalidTrick(format!(
                     "tick {:?} is not greater than last trick {:?}",
-                    players_trick,
-                    last_trick
-                ));
+                    players_trick, last_trick
+                )));
             }
             if let TrickType::FourOfAKind = players_trick_type {
                 return Ok(());
@@ -524,16 +1505,15 @@ pub fn compare_tricks(last_trick: &[Cards], players_trick: &[Cards]) -> anyhow::
             if let TrickType::StraightFlush = players_trick_type {
                 return Ok(());
             }
-            Err(anyhow!(
+            Err(GameError::InvalidTrick(format!(
                 "Trick type {:?} does not match {:?}",
-                players_trick_type,
-                last_trick_type
-            ))
+                players_trick_type, last_trick_type
+            )))
         }
         TrickType::Straight => {
             if let TrickType::Straight = players_trick_type {
                 if players_trick.len() != last_trick.len() {
-                    return Err(anyhow!("invalid trick"));
+                    return Err(GameError::InvalidTrick("invalid trick".into()));
                 }
                 let last_highest_number = last_trick.iter().map(|c| c.get_card_number()).max();
 
@@ -544,11 +1524,10 @@ pub fn compare_tricks(last_trick: &[Cards], players_trick: &[Cards]) -> anyhow::
                     return Ok(());
                 }
 
-                return Err(anyhow!(
+                return Err(GameError::InvalidTrick(format!(
                     "tick {:?} is not greater than last trick {:?}",
-                    players_trick,
-                    last_trick
-                ));
+                    players_trick, last_trick
+                )));
             }
             if let TrickType::FourOfAKind = players_trick_type {
                 return Ok(());
@@ -557,7 +1536,7 @@ pub fn compare_tricks(last_trick: &[Cards], players_trick: &[Cards]) -> anyhow::
             if let TrickType::StraightFlush = players_trick_type {
                 return Ok(());
             }
-            Err(anyhow!("invalid trick"))
+            Err(GameError::InvalidTrick("invalid trick".into()))
         }
         TrickType::FourOfAKind => {
             if let TrickType::FourOfAKind = players_trick_type {
@@ -565,22 +1544,21 @@ pub fn compare_tricks(last_trick: &[Cards], players_trick: &[Cards]) -> anyhow::
                     return Ok(());
                 }
 
-                return Err(anyhow!(
+                return Err(GameError::InvalidTrick(format!(
                     "tick {:?} is not greater than last trick {:?}",
-                    players_trick,
-                    last_trick
-                ));
+                    players_trick, last_trick
+                )));
             }
 
             if let TrickType::StraightFlush = players_trick_type {
                 return Ok(());
             }
-            Err(anyhow!("invalid trick"))
+            Err(GameError::InvalidTrick("invalid trick".into()))
         }
         TrickType::StraightFlush => {
             if let TrickType::StraightFlush = players_trick_type {
                 if players_trick.len() != last_trick.len() {
-                    return Err(anyhow!("invalid trick"));
+                    return Err(GameError::InvalidTrick("invalid trick".into()));
                 }
                 let mut last_trick = last_trick.to_owned();
                 let mut players_trick = players_trick.to_owned();
@@ -589,18 +1567,19 @@ pub fn compare_tricks(last_trick: &[Cards], players_trick: &[Cards]) -> anyhow::
                 if last_trick[0].get_card_number() < players_trick[0].get_card_number() {
                     return Ok(());
                 }
-                return Err(anyhow!(
+                return Err(GameError::InvalidTrick(format!(
                     "tick {:?} is not greater than last trick {:?}",
-                    players_trick,
-                    last_trick
-                ));
+                    players_trick, last_trick
+                )));
             }
-            Err(anyhow!("invalid trick"))
+            Err(GameError::InvalidTrick("invalid trick".into()))
         }
         TrickType::SequenceOfPairs => {
             if let TrickType::SequenceOfPairs = players_trick_type {
                 if players_trick.len() != last_trick.len() {
-                    return Err(anyhow!("trick length does not match"));
+                    return Err(GameError::InvalidTrick(
+                        "trick length does not match".into(),
+                    ));
                 }
                 let mut last_trick = last_trick.to_owned();
                 let mut players_trick = players_trick.to_owned();
@@ -609,11 +1588,10 @@ pub fn compare_tricks(last_trick: &[Cards], players_trick: &[Cards]) -> anyhow::
                 if last_trick[0].get_card_number() < players_trick[0].get_card_number() {
                     return Ok(());
                 }
-                return Err(anyhow!(
+                return Err(GameError::InvalidTrick(format!(
                     "tick {:?} is not greater than last trick {:?}",
-                    players_trick,
-                    last_trick
-                ));
+                    players_trick, last_trick
+                )));
             }
 
             if let TrickType::FourOfAKind = players_trick_type {
@@ -623,12 +1601,16 @@ pub fn compare_tricks(last_trick: &[Cards], players_trick: &[Cards]) -> anyhow::
             if let TrickType::StraightFlush = players_trick_type {
                 return Ok(());
             }
-            Err(anyhow!("invalid trick"))
+            Err(GameError::InvalidTrick("invalid trick".into()))
         }
     }
 }
 
 pub fn generate_hands() -> Vec<Hand> {
+    generate_hands_with(&mut rand::thread_rng())
+}
+
+pub fn generate_hands_with(rng: &mut impl Rng) -> Vec<Hand> {
     let mut deck: Vec<Cards> = Vec::with_capacity(56);
     for color in [Color::Black, Color::Blue, Color::Red, Color::Green] {
         deck.push(Cards::Two(color.clone()));
@@ -652,8 +1634,6 @@ pub fn generate_hands() -> Vec<Hand> {
 
     let mut hands: Vec<Hand> = Vec::with_capacity(4);
 
-    let mut rng = rand::thread_rng();
-
     for _ in 0..4 {
         let mut hand: Hand = Hand {
             cards: Vec::with_capacity(14),
@@ -666,6 +1646,58 @@ pub fn generate_hands() -> Vec<Hand> {
     hands
 }
 
+/// Sets the rank the phoenix stands for. As a single card the phoenix counts half a rank above
+/// the card it is played on, which is stored as the rank of that card. When leading, it counts
+/// as one and a half.
+pub fn resolve_phoenix(trick: &[Cards], last_trick: Option<&[Cards]>) -> GameResult<Vec<Cards>> {
+    let value = match (trick.len(), last_trick) {
+        (1, Some([last_card])) => last_card.get_card_number(),
+        (1, _) => Some(1),
+        _ => TrickType::resolve(trick)?.1,
+    };
+
+    Ok(trick
+        .iter()
+        .map(|c| match c {
+            Cards::Phoenix(_) => Cards::Phoenix(Box::new(Phoenix { value })),
+            _ => c.clone(),
+        })
+        .collect())
+}
+
+/// Returns the rank wished for with a mahjong in the trick. Only the ranks two to ace can be
+/// wished for.
+fn wished_rank(trick: &[Cards]) -> GameResult<Option<u8>> {
+    let wish = trick.iter().find_map(|c| match c {
+        Cards::Mahjong(mahjong) => mahjong.wish.as_ref(),
+        _ => None,
+    });
+
+    match wish {
+        None => Ok(None),
+        Some(Cards::Phoenix(_)) => Err(GameError::InvalidTrick(format!("invalid wish {:?}", wish))),
+        Some(card) => match card.get_card_number() {
+            Some(rank @ 2..=14) => Ok(Some(rank)),
+            _ => Err(GameError::InvalidTrick(format!("invalid wish {:?}", wish))),
+        },
+    }
+}
+
+pub fn fulfils_wish(trick: &[Cards], wish: u8) -> bool {
+    trick.iter().any(|c| c.has_rank(wish))
+}
+
+//every card of the hand can only be used once
 fn player_owns_cards(hand: &Hand, selected_cards: &[Cards]) -> bool {
-    selected_cards.iter().all(|card| hand.cards.contains(card))
+    let mut cards = hand.cards.clone();
+
+    selected_cards
+        .iter()
+        .all(|card| match cards.iter().position(|c| c == card) {
+            Some(i) => {
+                cards.remove(i);
+                true
+            }
+            None => false,
+        })
 }
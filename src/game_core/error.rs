@@ -0,0 +1,83 @@
+use serde::{ser::SerializeStruct, Serialize, Serializer};
+use thiserror::Error;
+
+pub type GameResult<T> = Result<T, GameError>;
+
+#[derive(Debug, Error)]
+pub enum GameError {
+    #[error("game not found")]
+    NotFound,
+    #[error("player not found")]
+    PlayerNotFound,
+    #[error("invalid session token")]
+    InvalidToken,
+    #[error("team is full")]
+    TeamFull,
+    #[error("invalid teams")]
+    InvalidTeams,
+    #[error("{0}")]
+    WrongPhase(String),
+    #[error("not your turn")]
+    NotYourTurn,
+    #[error("{0}")]
+    InvalidTrick(String),
+    #[error("the wish of the mahjong has to be fulfilled")]
+    WishNotFulfilled,
+    #[error("{0}")]
+    InvalidCall(String),
+    #[error("{0}")]
+    InvalidExchange(String),
+    #[error("the dragon has to be given away first")]
+    DragonNotGifted,
+    #[error("{0}")]
+    InvalidDragonGift(String),
+    #[error("{0}")]
+    InvalidConfig(String),
+    #[error("only the host can do that")]
+    NotHost,
+    #[error("not all players are connected")]
+    PlayersAway,
+    #[error("{0}")]
+    InvalidLog(String),
+    #[error("invalid request: {0}")]
+    InvalidRequest(String),
+    //broken invariants, these are bugs and not the fault of the player
+    #[error(transparent)]
+    Internal(#[from] anyhow::Error),
+}
+
+impl GameError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            GameError::NotFound => "not_found",
+            GameError::PlayerNotFound => "player_not_found",
+            GameError::InvalidToken => "invalid_token",
+            GameError::TeamFull => "team_full",
+            GameError::InvalidTeams => "invalid_teams",
+            GameError::WrongPhase(_) => "wrong_phase",
+            GameError::NotYourTurn => "not_your_turn",
+            GameError::InvalidTrick(_) => "invalid_trick",
+            GameError::WishNotFulfilled => "wish_not_fulfilled",
+            GameError::InvalidCall(_) => "invalid_call",
+            GameError::InvalidExchange(_) => "invalid_exchange",
+            GameError::DragonNotGifted => "dragon_not_gifted",
+            GameError::InvalidDragonGift(_) => "invalid_dragon_gift",
+            GameError::InvalidConfig(_) => "invalid_config",
+            GameError::NotHost => "not_host",
+            GameError::PlayersAway => "players_away",
+            GameError::InvalidLog(_) => "invalid_log",
+            GameError::InvalidRequest(_) => "invalid_request",
+            GameError::Internal(_) => "internal",
+        }
+    }
+}
+
+//sent to the clients as { code, message }
+impl Serialize for GameError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut error = serializer.serialize_struct("GameError", 2)?;
+        error.serialize_field("code", self.code())?;
+        error.serialize_field("message", &self.to_string())?;
+        error.end()
+    }
+}
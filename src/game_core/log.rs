@@ -0,0 +1,190 @@
+use std::collections::{BTreeMap, HashMap};
+
+use serde::{Deserialize, Serialize};
+
+use crate::game_core::core::{
+    Cards, Exchange, Game, GameConfig, GameError, GameResult, GameSummary, Player, PlayerId,
+    RoundSummary, Team, TrickWon, Turn,
+};
+
+/// Everything that happened in a game, in the order it happened. The log starts with the table
+/// as it was seated, so it can be replayed from the seed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "event")]
+pub enum Event {
+    #[serde(rename_all = "camelCase")]
+    Start {
+        game_id: String,
+        seed: u64,
+        config: GameConfig,
+        seats: Vec<Seat>,
+    },
+    /// All fourteen cards of every player, including the ones dealt after Grand Tichu.
+    Deal {
+        hands: BTreeMap<PlayerId, Vec<Cards>>,
+    },
+    GrandTichu {
+        player: PlayerId,
+        call: bool,
+    },
+    Tichu {
+        player: PlayerId,
+    },
+    Exchange(Exchange),
+    Turn(Turn),
+    DragonGift {
+        player: PlayerId,
+        recipient: PlayerId,
+    },
+    TrickWon(TrickWon),
+    RoundScored(RoundSummary),
+    GameOver(GameSummary),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Seat {
+    pub id: PlayerId,
+    pub username: String,
+    pub team: Option<Team>,
+    pub place: u8,
+}
+
+impl Event {
+    pub(crate) fn start(game: &Game) -> Event {
+        Event::Start {
+            game_id: game.game_id.clone(),
+            seed: game.seed,
+            //the seed is kept with the log itself
+            config: GameConfig {
+                seed: None,
+                ..game.config.clone()
+            },
+            seats: game
+                .seats()
+                .into_iter()
+                .map(|p| Seat {
+                    id: p.id,
+                    username: p.username.clone(),
+                    team: p.team.clone(),
+                    place: p.place,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// One event per line.
+pub fn to_json_lines(events: &[Event]) -> GameResult<String> {
+    let mut lines = String::new();
+
+    for event in events {
+        let line = serde_json::to_string(event).map_err(anyhow::Error::from)?;
+        lines.push_str(&line);
+        lines.push('\n');
+    }
+
+    Ok(lines)
+}
+
+pub fn from_json_lines(lines: &str) -> GameResult<Vec<Event>> {
+    lines
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            serde_json::from_str(line)
+                .map_err(|err| GameError::InvalidLog(format!("line {}: {}", i + 1, err)))
+        })
+        .collect()
+}
+
+/// Rebuilds a game by seating its players, dealing from its seed and taking every action of the
+/// log again. Fails if the game does not play out exactly as logged.
+pub fn replay(events: &[Event]) -> GameResult<Game> {
+    let (game_id, seed, config, seats) = match events.first() {
+        Some(Event::Start {
+            game_id,
+            seed,
+            config,
+            seats,
+        }) => (game_id, seed, config, seats),
+        _ => {
+            return Err(GameError::InvalidLog(
+                "the log has to start with the seated table".into(),
+            ))
+        }
+    };
+
+    let players = seats
+        .iter()
+        .map(|seat| {
+            let player = Player {
+                id: seat.id,
+                username: seat.username.clone(),
+                team: seat.team.clone(),
+                place: seat.place,
+                ..Default::default()
+            };
+            (seat.id, player)
+        })
+        .collect::<HashMap<_, _>>();
+
+    let team_count = |team: Team| {
+        players
+            .values()
+            .filter(|p| p.team == Some(team.clone()))
+            .count()
+    };
+
+    //dealing and the turn order need a full table
+    if players.len() != 4 || team_count(Team::One) != 2 || team_count(Team::Two) != 2 {
+        return Err(GameError::InvalidLog(
+            "the log has to start with four players in two teams".into(),
+        ));
+    }
+
+    let mut game = Game::new(game_id.clone(), players);
+    game.configure(config.clone())?;
+    game.seed = *seed;
+
+    for event in events.iter().skip(1) {
+        match event {
+            Event::Deal { .. } if game.phase.is_none() => {
+                game.begin_round()?;
+            }
+            Event::Deal { .. } => {
+                game.next_round()?;
+            }
+            Event::GrandTichu { player, call } => {
+                if game.call_grand_tichu(*player, *call)? {
+                    game.end_grand_tichu()?;
+                }
+            }
+            Event::Tichu { player } => game.call_tichu(*player)?,
+            Event::Exchange(exchange) => {
+                if game.submit_exchange(exchange.clone())? {
+                    game.finish_exchange()?;
+                }
+            }
+            Event::Turn(turn) => {
+                game.play_turn(turn.clone())?;
+            }
+            Event::DragonGift { player, recipient } => {
+                game.gift_dragon(*player, *recipient)?;
+            }
+            //these follow from the actions and are compared below
+            Event::Start { .. }
+            | Event::TrickWon(_)
+            | Event::RoundScored(_)
+            | Event::GameOver(_) => {}
+        }
+    }
+
+    if game.log != events {
+        return Err(GameError::InvalidLog(
+            "the game did not play out as logged".into(),
+        ));
+    }
+
+    Ok(game)
+}
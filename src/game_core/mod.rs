@@ -1,3 +1,13 @@
-pub(crate) mod core;
+pub mod bot;
+pub mod core;
+pub mod error;
+pub mod log;
+pub mod moves;
+#[allow(
+    clippy::module_inception,
+    clippy::bool_assert_comparison,
+    clippy::clone_on_copy,
+    clippy::useless_vec
+)]
 mod tests;
-pub(crate) mod types;
+pub mod types;
@@ -0,0 +1,158 @@
+use std::collections::BTreeMap;
+
+use crate::game_core::core::{compare_tricks, fulfils_wish, resolve_phoenix, Cards, Hand, Phoenix};
+
+/// Every play the hand can make on the last trick, or as the lead if there is none. A play with
+/// the phoenix is listed once for every rank it can stand for within a combination. While the
+/// wish can be fulfilled, only the plays fulfilling it are returned. Passing is not a play.
+pub fn legal_plays(hand: &Hand, last_trick: Option<&[Cards]>, wish: Option<u8>) -> Vec<Vec<Cards>> {
+    let mut plays = combinations(hand)
+        .into_iter()
+        .filter_map(|cards| resolve_phoenix(&cards, last_trick).ok())
+        .filter(|cards| {
+            last_trick.is_none_or(|last_trick| compare_tricks(last_trick, cards).is_ok())
+        })
+        .collect::<Vec<_>>();
+
+    if let Some(wish) = wish {
+        if plays.iter().any(|cards| fulfils_wish(cards, wish)) {
+            plays.retain(|cards| fulfils_wish(cards, wish));
+        }
+    }
+
+    plays
+}
+
+/// Every trick that can be formed from the hand, no matter what lies on the table.
+fn combinations(hand: &Hand) -> Vec<Vec<Cards>> {
+    let phoenix = hand.cards.iter().any(|c| matches!(c, Cards::Phoenix(_)));
+
+    //the mahjong counts as one, so it only shows up in straights
+    let mut ranks: BTreeMap<u8, Vec<Cards>> = BTreeMap::new();
+    for card in hand.cards.iter() {
+        if let (false, Some(rank)) = (matches!(card, Cards::Phoenix(_)), card.get_card_number()) {
+            ranks.entry(rank).or_default().push(card.clone());
+        }
+    }
+
+    let mut combinations = hand
+        .cards
+        .iter()
+        .map(|card| vec![card.clone()])
+        .collect::<Vec<_>>();
+
+    for rank in 2..=14 {
+        for size in 2..=3 {
+            combinations.extend(
+                groups(&ranks, rank, size, phoenix)
+                    .into_iter()
+                    .map(|(cards, _)| cards),
+            );
+        }
+
+        //the phoenix never completes a bomb
+        combinations.extend(
+            groups(&ranks, rank, 4, false)
+                .into_iter()
+                .map(|(cards, _)| cards),
+        );
+
+        for (triple, used) in groups(&ranks, rank, 3, phoenix) {
+            for pair_rank in (2..=14).filter(|pair_rank| *pair_rank != rank) {
+                for (pair, _) in groups(&ranks, pair_rank, 2, phoenix && !used) {
+                    combinations.push([triple.clone(), pair].concat());
+                }
+            }
+        }
+    }
+
+    for len in 5..=14 {
+        for start in 1..=15 - len {
+            combinations.extend(runs(&ranks, start, len, 1, phoenix));
+        }
+    }
+
+    for len in 2..=7 {
+        for start in 2..=15 - len {
+            combinations.extend(runs(&ranks, start, len, 2, phoenix));
+        }
+    }
+
+    combinations
+}
+
+/// Consecutive ranks with `width` cards each, the phoenix standing in at most once.
+fn runs(
+    ranks: &BTreeMap<u8, Vec<Cards>>,
+    start: u8,
+    len: u8,
+    width: usize,
+    phoenix: bool,
+) -> Vec<Vec<Cards>> {
+    let mut runs = vec![(Vec::new(), false)];
+
+    for rank in start..start + len {
+        runs = runs
+            .into_iter()
+            .flat_map(|(cards, used): (Vec<Cards>, bool)| {
+                groups(ranks, rank, width, phoenix && !used)
+                    .into_iter()
+                    .map(move |(group, with_phoenix)| {
+                        ([cards.clone(), group].concat(), used || with_phoenix)
+                    })
+            })
+            .collect();
+
+        if runs.is_empty() {
+            break;
+        }
+    }
+
+    runs.into_iter().map(|(cards, _)| cards).collect()
+}
+
+/// Every way to pick `size` cards of a rank, and whether the phoenix was needed for it.
+fn groups(
+    ranks: &BTreeMap<u8, Vec<Cards>>,
+    rank: u8,
+    size: usize,
+    phoenix: bool,
+) -> Vec<(Vec<Cards>, bool)> {
+    let cards = ranks
+        .get(&rank)
+        .map(|cards| cards.as_slice())
+        .unwrap_or_default();
+
+    let mut groups = subsets(cards, size)
+        .into_iter()
+        .map(|cards| (cards, false))
+        .collect::<Vec<_>>();
+
+    //the phoenix can stand for any rank from two to ace
+    if phoenix && (2..=14).contains(&rank) {
+        groups.extend(subsets(cards, size - 1).into_iter().map(|mut cards| {
+            cards.push(Cards::Phoenix(Box::new(Phoenix { value: Some(rank) })));
+            (cards, true)
+        }));
+    }
+
+    groups
+}
+
+fn subsets(cards: &[Cards], size: usize) -> Vec<Vec<Cards>> {
+    match (size, cards.split_first()) {
+        (0, _) => vec![Vec::new()],
+        (_, None) => Vec::new(),
+        (_, Some((first, rest))) => {
+            let mut with_first = subsets(rest, size - 1)
+                .into_iter()
+                .map(|mut cards| {
+                    cards.insert(0, first.clone());
+                    cards
+                })
+                .collect::<Vec<_>>();
+            with_first.extend(subsets(rest, size));
+            with_first
+        }
+    }
+}
@@ -4,20 +4,24 @@ mod tests {
 
     use socketioxide::socket::Sid;
 
+    use crate::game_core::bot;
     use crate::game_core::core::{
-        compare_tricks, generate_hands, Action, Cards, Color, Exchange, Game, Hand, Mahjong,
-        Phoenix, Player, Team, TrickType, Turn,
+        compare_tricks, generate_hands, resolve_phoenix, Action, BotKind, Call, Cards, Color,
+        Exchange, Game, GameConfig, GameError, Hand, Mahjong, Phase, Phoenix, Player, PlayerId,
+        Team, TrickType, TrickWon, Turn,
     };
+    use crate::game_core::log::{self, Event};
+    use crate::game_core::moves::legal_plays;
 
     fn dummy_game() -> Game {
         let mut players = HashMap::new();
         for i in 0..4 {
-            let socket_id = Sid::new();
+            let id = PlayerId::new_v4();
             if i < 2 {
                 players.insert(
-                    socket_id,
+                    id,
                     Player {
-                        socket_id,
+                        id,
                         username: i.to_string(),
                         team: Some(Team::One),
                         ..Default::default()
@@ -26,9 +30,9 @@ mod tests {
                 continue;
             } else {
                 players.insert(
-                    socket_id,
+                    id,
                     Player {
-                        socket_id,
+                        id,
                         username: i.to_string(),
                         team: Some(Team::Two),
                         ..Default::default()
@@ -39,105 +43,1492 @@ mod tests {
         Game::new("test_game".to_string(), players)
     }
 
+    fn deal_all_cards(game: &mut Game) {
+        game.deal_cards();
+        let ids = game.players.keys().cloned().collect::<Vec<_>>();
+        for id in ids {
+            game.deal_remaining_cards(id).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_generate_hands() {
+        let hands = generate_hands();
+        assert_eq!(hands.len(), 4);
+        for hand in hands.clone() {
+            assert_eq!(hand.cards.len(), 14);
+        }
+    }
+
+    #[test]
+    fn test_deal_cards() {
+        let mut game = dummy_game();
+        game.deal_cards();
+        for player in game.players.values() {
+            assert_eq!(player.hand.as_ref().unwrap().cards.len(), 8);
+            assert_eq!(player.undealt.as_ref().unwrap().len(), 6);
+        }
+
+        let ids = game.players.keys().cloned().collect::<Vec<_>>();
+        for id in ids {
+            game.deal_remaining_cards(id).unwrap();
+            assert!(game.deal_remaining_cards(id).is_err());
+        }
+
+        for player in game.players.values() {
+            assert_eq!(player.hand.as_ref().unwrap().cards.len(), 14);
+            assert!(player.undealt.is_none());
+        }
+    }
+
+    #[test]
+    fn test_seeded_deal() {
+        let hands = |game: &Game| {
+            game.seats()
+                .iter()
+                .map(|p| p.hand.clone().unwrap().cards)
+                .collect::<Vec<_>>()
+        };
+
+        let config = GameConfig {
+            seed: Some(7),
+            ..Default::default()
+        };
+
+        let mut game = dummy_game();
+        let mut other = dummy_game();
+        game.configure(config.clone()).unwrap();
+        other.configure(config).unwrap();
+        assert_eq!(game.seed, 7);
+
+        //the same seed deals the same cards to the same seats, round after round
+        game.deal_cards();
+        other.deal_cards();
+        assert_eq!(hands(&game), hands(&other));
+
+        let first = hands(&game);
+
+        //pinned, a changed deal for the same seed breaks the replay of every stored log
+        assert_eq!(
+            first[0],
+            vec![
+                Cards::Four(Color::Blue),
+                Cards::Jack(Color::Black),
+                Cards::Ten(Color::Black),
+                Cards::Phoenix(Box::new(Phoenix { value: None })),
+                Cards::King(Color::Blue),
+                Cards::Dragon,
+                Cards::Five(Color::Blue),
+                Cards::King(Color::Green),
+            ]
+        );
+
+        game.deal_cards();
+        other.deal_cards();
+        assert_eq!(game.deals, 2);
+        assert_eq!(hands(&game), hands(&other));
+        assert_ne!(hands(&game), first);
+
+        other.seed = 8;
+        other.deal_cards();
+        assert_ne!(hands(&game), hands(&other));
+
+        //the seed is only given away with the result
+        let config = serde_json::to_value(&game.config).unwrap();
+        assert!(config.get("seed").is_none());
+        assert_eq!(game.summary(), None);
+
+        game.winner = Some(Team::Two);
+        assert_eq!(game.summary().unwrap().seed, 7);
+    }
+
+    #[test]
+    fn test_view_for() {
+        let mut game = dummy_game();
+        deal_all_cards(&mut game);
+        game.start().unwrap();
+
+        let p1 = game.round.as_ref().unwrap().current_player;
+        let p2 = game.round.as_ref().unwrap().prev_next_player[&p1].id;
+        game.players.get_mut(&p2).unwrap().hand = Some(Hand {
+            cards: vec![Cards::Dog, Cards::Two(Color::Black)],
+        });
+        game.players.get_mut(&p2).unwrap().call = Some(Call::Tichu);
+
+        let view = game.view_for(p1).unwrap();
+
+        assert_eq!(view.hand.unwrap().cards.len(), 14);
+        assert_eq!(view.current_player, Some(p1));
+        assert!(view.trick.is_empty());
+        assert_eq!(view.players.len(), 4);
+
+        let opponent = view.players.iter().find(|p| p.id == p2).unwrap();
+        assert_eq!(opponent.card_count, 2);
+        assert_eq!(opponent.call, Some(Call::Tichu));
+
+        //only the own hand is part of the view
+        let json = serde_json::to_value(game.view_for(p1).unwrap()).unwrap();
+        assert!(json["players"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .all(|p| p.get("hand").is_none()));
+        assert_eq!(json["hand"]["cards"].as_array().unwrap().len(), 14);
+
+        assert!(game.view_for(PlayerId::new_v4()).is_err());
+    }
+
+    #[test]
+    fn test_rejoin() {
+        let mut game = dummy_game();
+        let player_id = *game.players.keys().next().unwrap();
+        let token = uuid::Uuid::new_v4();
+
+        let player = game.players.get_mut(&player_id).unwrap();
+        player.token = token;
+        let old_socket = player.socket_id;
+        assert_eq!(game.player_id(old_socket).unwrap(), player_id);
+
+        assert_eq!(game.mark_away(old_socket).unwrap(), player_id);
+        assert!(game.players[&player_id].away);
+        assert!(game.still_away(player_id, old_socket));
+
+        let new_socket = Sid::new();
+        assert!(game.player_id(new_socket).is_err());
+        assert!(game.rejoin(uuid::Uuid::new_v4(), new_socket).is_err());
+
+        assert_eq!(game.rejoin(token, new_socket).unwrap(), player_id);
+        assert_eq!(game.player_id(new_socket).unwrap(), player_id);
+        assert_eq!(game.players[&player_id].socket_id, new_socket);
+        assert!(!game.players[&player_id].away);
+        assert!(!game.still_away(player_id, old_socket));
+    }
+
+    #[test]
+    fn test_grand_tichu() {
+        let mut game = dummy_game();
+        game.deal_cards();
+
+        let ids = game.players.keys().cloned().collect::<Vec<_>>();
+
+        assert!(game.call_grand_tichu(ids[0], true).is_err());
+
+        game.phase = Some(Phase::GrandTichu);
+
+        assert!(!game.call_grand_tichu(ids[0], true).unwrap());
+        assert!(game.call_grand_tichu(ids[0], false).is_err());
+        assert!(!game.call_grand_tichu(ids[1], false).unwrap());
+        assert!(!game.call_grand_tichu(ids[2], false).unwrap());
+        assert!(game.call_grand_tichu(ids[3], false).unwrap());
+
+        assert_eq!(
+            game.players.get(&ids[0]).unwrap().call,
+            Some(Call::GrandTichu)
+        );
+        assert_eq!(game.players.get(&ids[1]).unwrap().call, None);
+
+        for player in game.players.values() {
+            assert_eq!(player.hand.as_ref().unwrap().cards.len(), 14);
+        }
+    }
+
+    #[test]
+    fn test_tichu() {
+        let mut game = dummy_game();
+        deal_all_cards(&mut game);
+        game.phase = Some(Phase::Exchanging);
+
+        let ids = game.players.keys().cloned().collect::<Vec<_>>();
+
+        assert!(game.call_tichu(ids[0]).is_ok());
+        assert!(game.call_tichu(ids[0]).is_err());
+        assert_eq!(game.players.get(&ids[0]).unwrap().call, Some(Call::Tichu));
+
+        game.players
+            .get_mut(&ids[1])
+            .unwrap()
+            .hand
+            .as_mut()
+            .unwrap()
+            .cards
+            .pop();
+
+        assert!(game.call_tichu(ids[1]).is_err());
+    }
+
+    #[test]
+    fn test_tichu_during_grand_tichu() {
+        let mut game = dummy_game();
+        game.deal_cards();
+        game.phase = Some(Phase::GrandTichu);
+
+        let ids = game.seats().iter().map(|p| p.id).collect::<Vec<_>>();
+
+        //with only eight cards tichu can not be called yet
+        assert!(game.call_tichu(ids[0]).is_err());
+
+        game.call_grand_tichu(ids[0], false).unwrap();
+        assert!(game.call_tichu(ids[0]).is_ok());
+        assert_eq!(game.players[&ids[0]].call, Some(Call::Tichu));
+
+        game.call_grand_tichu(ids[1], true).unwrap();
+        assert!(game.call_tichu(ids[1]).is_err());
+        assert_eq!(game.players[&ids[1]].call, Some(Call::GrandTichu));
+    }
+
+    #[test]
+    fn test_validate_exchange() {
+        let mut game = dummy_game();
+        deal_all_cards(&mut game);
+
+        let ids = game.players.keys().cloned().collect::<Vec<_>>();
+
+        for player in game.players.values() {
+            let valid_ids = ids
+                .iter()
+                .filter_map(|id| if *id != player.id { Some(*id) } else { None })
+                .collect::<Vec<PlayerId>>();
+
+            let cards = player
+                .hand
+                .clone()
+                .expect("player has no hand")
+                .cards
+                .iter()
+                .take(3)
+                .cloned()
+                .collect::<Vec<_>>();
+
+            let valid_player_card = valid_ids
+                .iter()
+                .cloned()
+                .zip(cards.iter().cloned())
+                .collect::<HashMap<PlayerId, Cards>>();
+
+            let exchange = Exchange {
+                player: player.id,
+                player_card: valid_player_card,
+            };
+
+            let result = game.validate_exchange(&exchange);
+            assert!(result.is_ok());
+
+            let identical_cards = [cards[0].clone(), cards[0].clone(), cards[0].clone()];
+
+            let invalid_player_card = valid_ids
+                .iter()
+                .cloned()
+                .zip(identical_cards)
+                .collect::<HashMap<PlayerId, Cards>>();
+
+            let invalid_exchange = Exchange {
+                player: player.id,
+                player_card: invalid_player_card,
+            };
+
+            let result = game.validate_exchange(&invalid_exchange);
+            assert!(result.is_err());
+
+            let mut invalid_users = valid_ids.clone();
+            invalid_users[0] = player.id;
+            let invalid_player_card = invalid_users
+                .iter()
+                .cloned()
+                .zip(cards.iter().cloned())
+                .collect::<HashMap<PlayerId, Cards>>();
+
+            let invalid_exchange = Exchange {
+                player: player.id,
+                player_card: invalid_player_card,
+            };
+
+            let result = game.validate_exchange(&invalid_exchange);
+            assert!(result.is_err());
+        }
+    }
+
+    #[test]
+    fn test_exchange_duplicate_cards() {
+        let mut game = dummy_game();
+        deal_all_cards(&mut game);
+        game.phase = Some(Phase::Exchanging);
+
+        let player = game.seats()[0].id;
+        let others = game
+            .seats()
+            .iter()
+            .filter(|p| p.id != player)
+            .map(|p| p.id)
+            .collect::<Vec<_>>();
+
+        game.players.get_mut(&player).unwrap().hand = Some(Hand {
+            cards: vec![
+                Cards::Two(Color::Black),
+                Cards::Two(Color::Red),
+                Cards::Three(Color::Black),
+            ],
+        });
+
+        //the sort used to keep both black twos, depending on the order of the map
+        for _ in 0..100 {
+            let player_card = others
+                .iter()
+                .cloned()
+                .zip([
+                    Cards::Two(Color::Black),
+                    Cards::Two(Color::Red),
+                    Cards::Two(Color::Black),
+                ])
+                .collect::<HashMap<_, _>>();
+
+            let exchange = Exchange {
+                player,
+                player_card,
+            };
+            assert!(matches!(
+                game.validate_exchange(&exchange),
+                Err(GameError::InvalidExchange(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_play_duplicate_cards() {
+        let mut game = dummy_game();
+        deal_all_cards(&mut game);
+        game.start().unwrap();
+
+        let p1 = game.round.as_ref().unwrap().current_player;
+        game.players.get_mut(&p1).unwrap().hand = Some(Hand {
+            cards: vec![Cards::Nine(Color::Red), Cards::Nine(Color::Black)],
+        });
+
+        let result = game.play_turn(Turn {
+            player: p1,
+            action: Action::Play,
+            cards: Some(vec![Cards::Nine(Color::Red), Cards::Nine(Color::Red)]),
+        });
+        assert!(matches!(result, Err(GameError::InvalidTrick(_))));
+        assert_eq!(game.players[&p1].hand.as_ref().unwrap().cards.len(), 2);
+    }
+
+    #[test]
+    fn test_call_scoring() {
+        let mut game = dummy_game();
+        deal_all_cards(&mut game);
+        game.start().unwrap();
+
+        let team_1 = game
+            .players
+            .values()
+            .filter(|p| p.team == Some(Team::One))
+            .map(|p| p.id)
+            .collect::<Vec<_>>();
+        let team_2 = game
+            .players
+            .values()
+            .filter(|p| p.team == Some(Team::Two))
+            .map(|p| p.id)
+            .collect::<Vec<_>>();
+
+        for player in game.players.values_mut() {
+            player.hand = None;
+        }
+
+        game.players.get_mut(&team_2[1]).unwrap().hand = Some(Hand {
+            cards: vec![Cards::Two(Color::Black)],
+        });
+
+        game.players.get_mut(&team_1[0]).unwrap().call = Some(Call::Tichu);
+        game.players.get_mut(&team_2[0]).unwrap().call = Some(Call::GrandTichu);
+        game.round.as_mut().unwrap().first_to_finish = Some(team_1[0]);
+
+        assert!(game.cleanup_round().unwrap().is_none());
+
+        assert_eq!(game.score_t1, 100);
+        assert_eq!(game.score_t2, -200);
+    }
+
+    #[test]
+    fn test_double_victory() {
+        let mut game = dummy_game();
+        deal_all_cards(&mut game);
+        game.start().unwrap();
+
+        let p1 = game.round.as_ref().unwrap().current_player;
+        let p2 = game.round.as_ref().unwrap().prev_next_player[&p1].id;
+        let p3 = game.round.as_ref().unwrap().prev_next_player[&p2].id;
+        let p4 = game.round.as_ref().unwrap().prev_next_player[&p3].id;
+
+        let hands = [
+            (p1, vec![Cards::Two(Color::Black)]),
+            (p2, vec![Cards::Five(Color::Black), Cards::King(Color::Red)]),
+            (p3, vec![Cards::Three(Color::Black)]),
+            (p4, vec![Cards::Ten(Color::Red), Cards::Dragon]),
+        ];
+
+        for (id, cards) in hands {
+            game.players.get_mut(&id).unwrap().hand = Some(Hand { cards });
+        }
+
+        let result = game.play_turn(Turn {
+            player: p1,
+            action: Action::Play,
+            cards: Some(vec![Cards::Two(Color::Black)]),
+        });
+        assert!(!result.unwrap());
+
+        let result = game.play_turn(Turn {
+            player: p2,
+            action: Action::Pass,
+            cards: None,
+        });
+        assert!(!result.unwrap());
+
+        let result = game.play_turn(Turn {
+            player: p3,
+            action: Action::Play,
+            cards: Some(vec![Cards::Three(Color::Black)]),
+        });
+        assert!(result.unwrap());
+
+        let team = game.players.get(&p1).unwrap().team.clone();
+        assert_eq!(game.round.as_ref().unwrap().double_victory, team);
+        assert_eq!(game.round.as_ref().unwrap().first_to_finish, Some(p1));
+
+        let (winner_score, loser_score) = match team {
+            Some(Team::One) => (game.score_t1, game.score_t2),
+            _ => (game.score_t2, game.score_t1),
+        };
+        assert_eq!(winner_score, 200);
+        assert_eq!(loser_score, 0);
+    }
+
+    #[test]
+    fn test_next_round() {
+        let mut game = dummy_game();
+        deal_all_cards(&mut game);
+        game.start().unwrap();
+
+        assert!(game.next_round().is_err());
+
+        let p1 = game.round.as_ref().unwrap().current_player;
+        let p2 = game.round.as_ref().unwrap().prev_next_player[&p1].id;
+        let p3 = game.round.as_ref().unwrap().prev_next_player[&p2].id;
+
+        game.players.get_mut(&p1).unwrap().hand = Some(Hand {
+            cards: vec![Cards::Two(Color::Black)],
+        });
+        game.players.get_mut(&p3).unwrap().hand = Some(Hand {
+            cards: vec![Cards::Three(Color::Black)],
+        });
+        game.players.get_mut(&p1).unwrap().call = Some(Call::Tichu);
+
+        game.play_turn(Turn {
+            player: p1,
+            action: Action::Play,
+            cards: Some(vec![Cards::Two(Color::Black)]),
+        })
+        .unwrap();
+        game.play_turn(Turn {
+            player: p2,
+            action: Action::Pass,
+            cards: None,
+        })
+        .unwrap();
+        assert!(game
+            .play_turn(Turn {
+                player: p3,
+                action: Action::Play,
+                cards: Some(vec![Cards::Three(Color::Black)]),
+            })
+            .unwrap());
+
+        assert!(game.round_over());
+        let summary = game.round_summary().unwrap();
+        let winner_points = match game.players.get(&p1).unwrap().team {
+            Some(Team::One) => summary.points_t1,
+            _ => summary.points_t2,
+        };
+        assert_eq!(winner_points, 300);
+        assert_eq!(summary.score_t1, game.score_t1);
+        assert_eq!(summary.score_t2, game.score_t2);
+
+        //no more turns once the round is scored
+        assert!(game
+            .play_turn(Turn {
+                player: p2,
+                action: Action::Pass,
+                cards: None,
+            })
+            .is_err());
+
+        game.next_round().unwrap();
+
+        assert!(game.round.is_none());
+        assert!(matches!(game.phase, Some(Phase::GrandTichu)));
+        assert_eq!(game.winner, None);
+        for player in game.players.values() {
+            assert_eq!(player.hand.as_ref().unwrap().cards.len(), 8);
+            assert_eq!(player.undealt.as_ref().unwrap().len(), 6);
+            assert_eq!(player.call, None);
+            assert_eq!(player.trick_points, 0);
+            assert!(player.exchange.is_none());
+        }
+    }
+
+    #[test]
+    fn test_game_over() {
+        let mut game = dummy_game();
+        deal_all_cards(&mut game);
+        game.start().unwrap();
+
+        let p1 = game.round.as_ref().unwrap().current_player;
+        let team = game.players.get(&p1).unwrap().team.clone().unwrap();
+        game.round.as_mut().unwrap().first_to_finish = Some(p1);
+        game.round.as_mut().unwrap().double_victory = Some(team.clone());
+
+        //a tie at 1000 points or more does not end the game
+        game.score_t1 = 800;
+        game.score_t2 = 800;
+        match team {
+            Team::One => game.score_t2 = 1000,
+            _ => game.score_t1 = 1000,
+        }
+        assert_eq!(game.cleanup_round().unwrap(), None);
+        assert_eq!(game.winner, None);
+
+        game.next_round().unwrap();
+        deal_all_cards(&mut game);
+        game.phase = Some(Phase::Playing);
+        game.start().unwrap();
+
+        let p1 = game.round.as_ref().unwrap().current_player;
+        let team = game.players.get(&p1).unwrap().team.clone().unwrap();
+        game.round.as_mut().unwrap().first_to_finish = Some(p1);
+        game.round.as_mut().unwrap().double_victory = Some(team.clone());
+
+        assert_eq!(game.cleanup_round().unwrap(), Some(team.clone()));
+        assert_eq!(game.winner, Some(team));
+        assert!(game.next_round().is_err());
+    }
+
+    #[test]
+    fn test_game_config() {
+        let mut game = dummy_game();
+        game.config = GameConfig {
+            target_score: 200,
+            grand_tichu: false,
+            ..Default::default()
+        };
+
+        //without grand tichu all cards are dealt at once
+        assert!(matches!(game.begin_round().unwrap(), Phase::Exchanging));
+        for player in game.players.values() {
+            assert_eq!(player.hand.as_ref().unwrap().cards.len(), 14);
+            assert!(player.undealt.is_none());
+        }
+
+        game.start().unwrap();
+        let p1 = game.round.as_ref().unwrap().current_player;
+        let team = game.players.get(&p1).unwrap().team.clone().unwrap();
+        game.round.as_mut().unwrap().first_to_finish = Some(p1);
+        game.round.as_mut().unwrap().double_victory = Some(team.clone());
+
+        assert_eq!(game.cleanup_round().unwrap(), Some(team));
+
+        //without the exchange the cards are played right away
+        let mut game = dummy_game();
+        game.config.grand_tichu = false;
+        game.config.exchange = false;
+
+        assert!(matches!(game.begin_round().unwrap(), Phase::Playing));
+        assert!(game.round.is_some());
+
+        let invalid = GameConfig {
+            target_score: 0,
+            ..Default::default()
+        };
+        assert!(matches!(
+            invalid.validate(),
+            Err(GameError::InvalidConfig(_))
+        ));
+    }
+
+    #[test]
+    fn test_dog_lead() {
+        let mut game = dummy_game();
+        deal_all_cards(&mut game);
+        game.start().unwrap();
+
+        let p1 = game.round.as_ref().unwrap().current_player;
+        let p2 = game.round.as_ref().unwrap().prev_next_player[&p1].id;
+        let p3 = game.round.as_ref().unwrap().prev_next_player[&p2].id;
+        let p4 = game.round.as_ref().unwrap().prev_next_player[&p3].id;
+
+        game.players.get_mut(&p1).unwrap().hand = Some(Hand {
+            cards: vec![Cards::Dog, Cards::Two(Color::Black)],
+        });
+
+        let dog = Turn {
+            player: p1,
+            action: Action::Play,
+            cards: Some(vec![Cards::Dog]),
+        };
+
+        //the trick won before is not announced again after the dog
+        game.round.as_mut().unwrap().trick_won = Some(TrickWon {
+            winner: p2,
+            points: 10,
+        });
+
+        assert!(!game.play_turn(dog.clone()).unwrap());
+
+        let round = game.round.as_ref().unwrap();
+        assert_eq!(round.current_player, p3);
+        assert!(round.current_trick.is_empty());
+        assert_eq!(round.current_trick_type, None);
+        assert_eq!(round.trick_won, None);
+
+        //the partner already finished, so the lead goes to the next player after them
+        game.players.get_mut(&p3).unwrap().hand = None;
+        game.round.as_mut().unwrap().mark_finished(p3);
+        game.round.as_mut().unwrap().current_player = p1;
+        game.players.get_mut(&p1).unwrap().hand = Some(Hand {
+            cards: vec![Cards::Dog, Cards::Two(Color::Black)],
+        });
+
+        assert!(!game.play_turn(dog).unwrap());
+        assert_eq!(game.round.as_ref().unwrap().current_player, p4);
+
+        game.players.get_mut(&p4).unwrap().hand = Some(Hand {
+            cards: vec![Cards::Three(Color::Black), Cards::Four(Color::Black)],
+        });
+        game.play_turn(Turn {
+            player: p4,
+            action: Action::Play,
+            cards: Some(vec![Cards::Three(Color::Black)]),
+        })
+        .unwrap();
+
+        //the dog can not be played on a trick
+        game.round.as_mut().unwrap().current_player = p1;
+        game.players.get_mut(&p1).unwrap().hand = Some(Hand {
+            cards: vec![Cards::Dog],
+        });
+        let result = game.play_turn(Turn {
+            player: p1,
+            action: Action::Play,
+            cards: Some(vec![Cards::Dog]),
+        });
+        assert!(result.is_err());
+        assert!(TrickType::try_from([Cards::Dog, Cards::Two(Color::Black)].as_slice()).is_err());
+    }
+
+    #[test]
+    fn test_dragon_gift() {
+        let mut game = dummy_game();
+        deal_all_cards(&mut game);
+        game.start().unwrap();
+
+        let p1 = game.round.as_ref().unwrap().current_player;
+        let p2 = game.round.as_ref().unwrap().prev_next_player[&p1].id;
+        let p3 = game.round.as_ref().unwrap().prev_next_player[&p2].id;
+        let p4 = game.round.as_ref().unwrap().prev_next_player[&p3].id;
+
+        game.players.get_mut(&p1).unwrap().hand = Some(Hand {
+            cards: vec![Cards::Dragon, Cards::Two(Color::Black)],
+        });
+
+        let result = game.play_turn(Turn {
+            player: p1,
+            action: Action::Play,
+            cards: Some(vec![Cards::Dragon]),
+        });
+        assert!(!result.unwrap());
+
+        for player in [p2, p3, p4] {
+            let pass = Turn {
+                player,
+                action: Action::Pass,
+                cards: None,
+            };
+            assert_eq!(game.play_turn(pass).unwrap(), player == p4);
+        }
+
+        assert_eq!(game.round.as_ref().unwrap().dragon_gift, Some(p1));
+
+        let blocked = game.play_turn(Turn {
+            player: p1,
+            action: Action::Play,
+            cards: Some(vec![Cards::Two(Color::Black)]),
+        });
+        assert!(blocked.is_err());
+
+        assert!(game.gift_dragon(p2, p4).is_err());
+        assert!(game.gift_dragon(p1, p3).is_err());
+        assert!(!game.gift_dragon(p1, p2).unwrap());
+
+        let round = game.round.as_ref().unwrap();
+        assert_eq!(round.dragon_gift, None);
+        assert!(round.current_trick.is_empty());
+        assert_eq!(round.current_player, p1);
+        assert_eq!(game.players[&p2].trick_points, 25);
+        assert_eq!(game.players[&p1].trick_points, 0);
+    }
+
+    #[test]
+    fn test_auto_gift_dragon() {
+        let mut game = dummy_game();
+        deal_all_cards(&mut game);
+        game.start().unwrap();
+
+        let p1 = game.round.as_ref().unwrap().current_player;
+        let p2 = game.round.as_ref().unwrap().prev_next_player[&p1].id;
+        let p3 = game.round.as_ref().unwrap().prev_next_player[&p2].id;
+        let p4 = game.round.as_ref().unwrap().prev_next_player[&p3].id;
+
+        assert!(game.auto_gift_dragon().is_err());
+
+        game.players.get_mut(&p1).unwrap().hand = Some(Hand {
+            cards: vec![Cards::Dragon, Cards::Two(Color::Black)],
+        });
+        game.players
+            .get_mut(&p4)
+            .unwrap()
+            .hand
+            .as_mut()
+            .unwrap()
+            .cards
+            .truncate(1);
+
+        game.play_turn(Turn {
+            player: p1,
+            action: Action::Play,
+            cards: Some(vec![Cards::Dragon]),
+        })
+        .unwrap();
+
+        for _ in 0..3 {
+            game.auto_turn().unwrap();
+        }
+
+        //the opponent with more cards left gets the trick
+        assert_eq!(game.auto_gift_dragon().unwrap(), (p1, p2));
+        assert_eq!(game.players[&p2].trick_points, 25);
+        assert_eq!(game.round.as_ref().unwrap().current_player, p1);
+    }
+
+    #[test]
+    fn test_add_bot() {
+        let mut game = dummy_game();
+        let human = *game.players.keys().next().unwrap();
+        game.players.retain(|id, _| *id == human);
+        game.players.get_mut(&human).unwrap().team = Some(Team::One);
+        game.players.get_mut(&human).unwrap().place = 1;
+
+        let bots = (0..3)
+            .map(|_| game.add_bot(BotKind::Rules).unwrap())
+            .collect::<Vec<_>>();
+        assert!(matches!(
+            game.add_bot(BotKind::Rules),
+            Err(GameError::TeamFull)
+        ));
+
+        assert_eq!(game.players[&bots[0]].team, Some(Team::One));
+        assert_eq!(game.players[&bots[1]].team, Some(Team::Two));
+        assert_eq!(game.players[&bots[2]].team, Some(Team::Two));
+        assert_eq!(game.players[&bots[2]].username, "Bot 3");
+
+        assert!(game.remove_bot(human).is_err());
+        game.remove_bot(bots[1]).unwrap();
+
+        //the free seat and name are taken again
+        let bot = game.add_bot(BotKind::Rules).unwrap();
+        assert_eq!(game.players[&bot].team, Some(Team::Two));
+        assert_eq!(game.players[&bot].username, "Bot 2");
+        assert_eq!(game.players[&bot].place, 3);
+    }
+
+    #[test]
+    fn test_join_lobby_with_bots() {
+        let host = Player {
+            id: PlayerId::new_v4(),
+            username: "host".to_string(),
+            is_host: true,
+            team: Some(Team::One),
+            place: 1,
+            ..Default::default()
+        };
+        let mut game = Game::new("test_game".to_string(), HashMap::from([(host.id, host)]));
+
+        let bot = game.add_bot(BotKind::Rules).unwrap();
+        let human = game.join_lobby("human".to_string(), Sid::new()).unwrap();
+        game.add_bot(BotKind::Rules).unwrap();
+
+        assert_eq!(game.players[&bot].team, Some(Team::One));
+        assert_eq!(human.team, Some(Team::Two));
+        assert_eq!(human.place, 3);
+        assert!(matches!(
+            game.join_lobby("late".to_string(), Sid::new()),
+            Err(GameError::TeamFull)
+        ));
+
+        //the seat of the bot is taken by the next human
+        game.remove_bot(bot).unwrap();
+        let next = game.join_lobby("next".to_string(), Sid::new()).unwrap();
+        assert_eq!(next.team, Some(Team::One));
+        assert_eq!(next.place, 2);
+
+        let places = game.seats().iter().map(|p| p.place).collect::<Vec<_>>();
+        assert_eq!(places, vec![1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_host_leaves_lobby() {
+        let host = Player {
+            id: PlayerId::new_v4(),
+            username: "host".to_string(),
+            is_host: true,
+            team: Some(Team::One),
+            place: 1,
+            ..Default::default()
+        };
+        let host_id = host.id;
+        let mut game = Game::new("test_game".to_string(), HashMap::from([(host.id, host)]));
+
+        game.add_bot(BotKind::Rules).unwrap();
+        let human = game.join_lobby("human".to_string(), Sid::new()).unwrap();
+        let other = game.join_lobby("other".to_string(), Sid::new()).unwrap();
+
+        //the host is handed to the human on the lowest seat
+        assert_eq!(game.leave_lobby(other.id).unwrap(), None);
+        assert_eq!(game.leave_lobby(host_id).unwrap(), Some(human.id));
+        assert!(game.players[&human.id].is_host);
+        assert_eq!(game.players.values().filter(|p| p.is_host).count(), 1);
+
+        assert_eq!(game.leave_lobby(human.id).unwrap(), None);
+        assert!(game.players.values().all(|p| p.bot.is_some()));
+        assert!(matches!(
+            game.leave_lobby(human.id),
+            Err(GameError::PlayerNotFound)
+        ));
+    }
+
+    #[test]
+    fn test_bots_play_rounds() {
+        let mut game = dummy_game();
+        game.config.target_score = i16::MAX;
+        for player in game.players.values_mut() {
+            player.bot = Some(BotKind::Rules);
+        }
+
+        game.begin_round().unwrap();
+
+        for _ in 0..20 {
+            bot::drive(&mut game).unwrap();
+
+            assert!(game.round_over());
+            assert!(game.players.values().filter(|p| p.hand.is_some()).count() <= 2);
+
+            game.next_round().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_event_log() {
+        let mut game = dummy_game();
+        game.config.target_score = 200;
+        for player in game.players.values_mut() {
+            player.bot = Some(BotKind::Rules);
+        }
+
+        game.begin_round().unwrap();
+
+        loop {
+            bot::drive(&mut game).unwrap();
+
+            if game.winner.is_some() {
+                break;
+            }
+            game.next_round().unwrap();
+        }
+
+        assert!(matches!(game.log.first(), Some(Event::Start { .. })));
+        assert_eq!(
+            game.log.last(),
+            Some(&Event::GameOver(game.summary().unwrap()))
+        );
+
+        let count = |f: fn(&Event) -> bool| game.log.iter().filter(|e| f(e)).count();
+        assert_eq!(
+            count(|e| matches!(e, Event::Deal { .. })),
+            count(|e| matches!(e, Event::RoundScored(_)))
+        );
+        assert!(count(|e| matches!(e, Event::TrickWon(_))) > 0);
+
+        //failed actions are not logged
+        let len = game.log.len();
+        let player = *game.players.keys().next().unwrap();
+        assert!(game
+            .play_turn(Turn {
+                player,
+                action: Action::Pass,
+                cards: None,
+            })
+            .is_err());
+        assert_eq!(game.log.len(), len);
+
+        let lines = log::to_json_lines(&game.log).unwrap();
+        assert_eq!(lines.lines().count(), len);
+        let events = log::from_json_lines(&lines).unwrap();
+        assert_eq!(events, game.log);
+
+        let replayed = log::replay(&events).unwrap();
+        assert_eq!(replayed.summary(), game.summary());
+        assert_eq!(replayed.deals, game.deals);
+
+        //a log missing a turn does not play out
+        let turn = events
+            .iter()
+            .position(|e| matches!(e, Event::Turn(_)))
+            .unwrap();
+        let mut tampered = events.clone();
+        tampered.remove(turn);
+        assert!(log::replay(&tampered).is_err());
+        assert!(matches!(
+            log::replay(&events[1..]),
+            Err(GameError::InvalidLog(_))
+        ));
+        assert!(log::from_json_lines("{}").is_err());
+
+        //a log without a full table is rejected before anything is dealt
+        let empty_table = vec![
+            Event::Start {
+                game_id: "test_game".to_string(),
+                seed: 0,
+                config: GameConfig {
+                    grand_tichu: false,
+                    exchange: false,
+                    ..Default::default()
+                },
+                seats: vec![],
+            },
+            events[1].clone(),
+        ];
+        assert!(matches!(
+            log::replay(&empty_table),
+            Err(GameError::InvalidLog(_))
+        ));
+    }
+
+    #[test]
+    fn test_trick_won() {
+        let mut game = dummy_game();
+        deal_all_cards(&mut game);
+        game.start().unwrap();
+
+        let p1 = game.round.as_ref().unwrap().current_player;
+        let p2 = game.round.as_ref().unwrap().prev_next_player[&p1].id;
+        let p3 = game.round.as_ref().unwrap().prev_next_player[&p2].id;
+        let p4 = game.round.as_ref().unwrap().prev_next_player[&p3].id;
+
+        game.players.get_mut(&p1).unwrap().hand = Some(Hand {
+            cards: vec![Cards::Five(Color::Black)],
+        });
+
+        let result = game.play_turn(Turn {
+            player: p1,
+            action: Action::Play,
+            cards: Some(vec![Cards::Five(Color::Black)]),
+        });
+        assert!(!result.unwrap());
+        assert!(game.players[&p1].hand.is_none());
+
+        for player in [p2, p3, p4] {
+            let pass = Turn {
+                player,
+                action: Action::Pass,
+                cards: None,
+            };
+            assert_eq!(game.play_turn(pass).unwrap(), player == p4);
+        }
+
+        //the winner went out, so the lead goes to the next player with cards
+        let round = game.round.as_ref().unwrap();
+        assert!(round.current_trick.is_empty());
+        assert_eq!(round.current_player, p2);
+        assert_eq!(
+            round.trick_won,
+            Some(TrickWon {
+                winner: p1,
+                points: 5
+            })
+        );
+        assert_eq!(game.players[&p1].trick_points, 5);
+        assert!(!game.round_over());
+    }
+
+    #[test]
+    fn test_mahjong_wish() {
+        let mut game = dummy_game();
+        deal_all_cards(&mut game);
+        game.start().unwrap();
+
+        let p1 = game.round.as_ref().unwrap().current_player;
+        let p2 = game.round.as_ref().unwrap().prev_next_player[&p1].id;
+        let p3 = game.round.as_ref().unwrap().prev_next_player[&p2].id;
+        let p4 = game.round.as_ref().unwrap().prev_next_player[&p3].id;
+
+        let hands = [
+            (
+                p1,
+                vec![
+                    Cards::Mahjong(Box::new(Mahjong { wish: None })),
+                    Cards::Two(Color::Black),
+                ],
+            ),
+            (p2, vec![Cards::Three(Color::Red), Cards::Nine(Color::Red)]),
+            (
+                p3,
+                vec![Cards::Seven(Color::Black), Cards::Eight(Color::Red)],
+            ),
+            (p4, vec![Cards::Seven(Color::Red), Cards::Ace(Color::Red)]),
+        ];
+
+        for (id, cards) in hands {
+            game.players.get_mut(&id).unwrap().hand = Some(Hand { cards });
+        }
+
+        let invalid_wish = Turn {
+            player: p1,
+            action: Action::Play,
+            cards: Some(vec![Cards::Mahjong(Box::new(Mahjong {
+                wish: Some(Cards::Dragon),
+            }))]),
+        };
+        assert!(game.play_turn(invalid_wish).is_err());
+        assert_eq!(game.players[&p1].hand.as_ref().unwrap().cards.len(), 2);
+
+        let wish = Turn {
+            player: p1,
+            action: Action::Play,
+            cards: Some(vec![Cards::Mahjong(Box::new(Mahjong {
+                wish: Some(Cards::Seven(Color::Green)),
+            }))]),
+        };
+        assert!(!game.play_turn(wish).unwrap());
+        assert_eq!(game.round.as_ref().unwrap().wish, Some(7));
+        assert_eq!(game.round.as_ref().unwrap().trick_state().wish, Some(7));
+
+        //p2 does not hold a seven and may play anything
+        let result = game.play_turn(Turn {
+            player: p2,
+            action: Action::Play,
+            cards: Some(vec![Cards::Three(Color::Red)]),
+        });
+        assert!(!result.unwrap());
+
+        //p3 holds a seven that beats the three, so passing or playing another card is not allowed
+        let pass = Turn {
+            player: p3,
+            action: Action::Pass,
+            cards: None,
+        };
+        assert!(game.play_turn(pass).is_err());
+
+        let eight = Turn {
+            player: p3,
+            action: Action::Play,
+            cards: Some(vec![Cards::Eight(Color::Red)]),
+        };
+        assert!(game.play_turn(eight).is_err());
+
+        let seven = Turn {
+            player: p3,
+            action: Action::Play,
+            cards: Some(vec![Cards::Seven(Color::Black)]),
+        };
+        assert!(!game.play_turn(seven).unwrap());
+        assert_eq!(game.round.as_ref().unwrap().wish, None);
+
+        //the wish is fulfilled, p4 may keep the seven
+        let pass = Turn {
+            player: p4,
+            action: Action::Pass,
+            cards: None,
+        };
+        assert!(!game.play_turn(pass).unwrap());
+    }
+
+    #[test]
+    fn test_auto_turn() {
+        let mut game = dummy_game();
+        deal_all_cards(&mut game);
+        game.start().unwrap();
+
+        let p1 = game.round.as_ref().unwrap().current_player;
+        let p2 = game.round.as_ref().unwrap().prev_next_player[&p1].id;
+        let p3 = game.round.as_ref().unwrap().prev_next_player[&p2].id;
+        let p4 = game.round.as_ref().unwrap().prev_next_player[&p3].id;
+
+        let hands = [
+            (
+                p1,
+                vec![
+                    Cards::King(Color::Black),
+                    Cards::Mahjong(Box::new(Mahjong { wish: None })),
+                ],
+            ),
+            (p2, vec![Cards::Ace(Color::Red), Cards::Nine(Color::Red)]),
+            (p3, vec![Cards::Two(Color::Black), Cards::Dragon]),
+            (p4, vec![Cards::Seven(Color::Red), Cards::Ace(Color::Blue)]),
+        ];
+
+        for (id, cards) in hands {
+            game.players.get_mut(&id).unwrap().hand = Some(Hand { cards });
+        }
+
+        //the leader plays their lowest single
+        let (turn, trick_over) = game.auto_turn().unwrap();
+        assert_eq!(turn.player, p1);
+        assert_eq!(
+            turn.cards,
+            Some(vec![Cards::Mahjong(Box::new(Mahjong { wish: None }))])
+        );
+        assert!(!trick_over);
+
+        //everyone else passes
+        let (turn, _) = game.auto_turn().unwrap();
+        assert_eq!(turn.player, p2);
+        assert_eq!(turn.action, Action::Pass);
+
+        game.round.as_mut().unwrap().wish = Some(14);
+
+        //p3 holds no ace and passes, p4 has to fulfil the wish
+        let (turn, _) = game.auto_turn().unwrap();
+        assert_eq!(turn.player, p3);
+        assert_eq!(turn.action, Action::Pass);
+
+        let (turn, _) = game.auto_turn().unwrap();
+        assert_eq!(turn.player, p4);
+        assert_eq!(turn.cards, Some(vec![Cards::Ace(Color::Blue)]));
+        assert_eq!(game.round.as_ref().unwrap().wish, None);
+    }
+
+    #[test]
+    fn test_mahjong_wish_not_playable() {
+        let mut game = dummy_game();
+        deal_all_cards(&mut game);
+        game.start().unwrap();
+
+        let p1 = game.round.as_ref().unwrap().current_player;
+        let p2 = game.round.as_ref().unwrap().prev_next_player[&p1].id;
+
+        let hands = [
+            (
+                p1,
+                vec![
+                    Cards::Mahjong(Box::new(Mahjong { wish: None })),
+                    Cards::Two(Color::Black),
+                    Cards::Three(Color::Black),
+                    Cards::Four(Color::Black),
+                    Cards::Five(Color::Black),
+                    Cards::Ace(Color::Black),
+                ],
+            ),
+            (p2, vec![Cards::Eight(Color::Red), Cards::Nine(Color::Red)]),
+        ];
+
+        for (id, cards) in hands {
+            game.players.get_mut(&id).unwrap().hand = Some(Hand { cards });
+        }
+
+        let straight = Turn {
+            player: p1,
+            action: Action::Play,
+            cards: Some(vec![
+                Cards::Mahjong(Box::new(Mahjong {
+                    wish: Some(Cards::Eight(Color::Black)),
+                })),
+                Cards::Two(Color::Black),
+                Cards::Three(Color::Black),
+                Cards::Four(Color::Black),
+                Cards::Five(Color::Black),
+            ]),
+        };
+        assert!(!game.play_turn(straight).unwrap());
+        assert_eq!(game.round.as_ref().unwrap().wish, Some(8));
+
+        //p2 holds an eight but can not play it within a straight
+        let pass = Turn {
+            player: p2,
+            action: Action::Pass,
+            cards: None,
+        };
+        assert!(!game.play_turn(pass).unwrap());
+        assert_eq!(game.round.as_ref().unwrap().wish, Some(8));
+    }
+
+    #[test]
+    fn test_bomb_out_of_turn() {
+        let mut game = dummy_game();
+        deal_all_cards(&mut game);
+        game.start().unwrap();
+
+        let p1 = game.round.as_ref().unwrap().current_player;
+        let p2 = game.round.as_ref().unwrap().prev_next_player[&p1].id;
+        let p3 = game.round.as_ref().unwrap().prev_next_player[&p2].id;
+        let p4 = game.round.as_ref().unwrap().prev_next_player[&p3].id;
+
+        let bomb = vec![
+            Cards::Three(Color::Black),
+            Cards::Three(Color::Blue),
+            Cards::Three(Color::Red),
+            Cards::Three(Color::Green),
+        ];
+
+        let mut p4_cards = bomb.clone();
+        p4_cards.push(Cards::Ace(Color::Black));
+
+        let hands = [
+            (p1, vec![Cards::Two(Color::Black), Cards::Six(Color::Black)]),
+            (p4, p4_cards),
+        ];
+
+        for (id, cards) in hands {
+            game.players.get_mut(&id).unwrap().hand = Some(Hand { cards });
+        }
+
+        //a bomb can not be used to lead out of turn
+        let early_bomb = Turn {
+            player: p4,
+            action: Action::Play,
+            cards: Some(bomb.clone()),
+        };
+        assert!(game.play_turn(early_bomb).is_err());
+
+        let result = game.play_turn(Turn {
+            player: p1,
+            action: Action::Play,
+            cards: Some(vec![Cards::Two(Color::Black)]),
+        });
+        assert!(!result.unwrap());
+        assert_eq!(game.round.as_ref().unwrap().current_player, p2);
+
+        let out_of_turn = Turn {
+            player: p4,
+            action: Action::Play,
+            cards: Some(vec![Cards::Ace(Color::Black)]),
+        };
+        assert!(game.play_turn(out_of_turn).is_err());
+
+        let bomb_turn = Turn {
+            player: p4,
+            action: Action::Play,
+            cards: Some(bomb),
+        };
+        assert!(!game.play_turn(bomb_turn).unwrap());
+
+        let round = game.round.as_ref().unwrap();
+        assert_eq!(round.last_played_player, p4);
+        assert_eq!(round.current_player, p1);
+        assert_eq!(round.current_trick_type, Some(TrickType::FourOfAKind));
+
+        //the house rule can forbid interrupting with a bomb
+        game.config.house_rules.bombs_out_of_turn = false;
+        let out_of_turn = Turn {
+            player: p3,
+            action: Action::Play,
+            cards: Some(vec![
+                Cards::Two(Color::Black),
+                Cards::Three(Color::Black),
+                Cards::Four(Color::Black),
+                Cards::Five(Color::Black),
+                Cards::Six(Color::Black),
+            ]),
+        };
+        assert!(matches!(
+            game.play_turn(out_of_turn),
+            Err(GameError::NotYourTurn)
+        ));
+    }
+
     #[test]
-    fn test_generate_hands() {
-        let hands = generate_hands();
-        assert_eq!(hands.len(), 4);
-        for hand in hands.clone() {
+    fn test_exchange_cards() {
+        let mut game = dummy_game();
+        deal_all_cards(&mut game);
+        game.phase = Some(Phase::Exchanging);
+
+        let ids = game.players.keys().cloned().collect::<Vec<_>>();
+        let hands_before = game
+            .players
+            .iter()
+            .map(|(sid, p)| (*sid, p.hand.clone().unwrap()))
+            .collect::<HashMap<_, _>>();
+
+        for (i, id) in ids.iter().enumerate() {
+            let player = game.players.get(id).unwrap();
+            let player_card = ids
+                .iter()
+                .filter(|other| *other != id)
+                .cloned()
+                .zip(player.hand.as_ref().unwrap().cards.iter().take(3).cloned())
+                .collect::<HashMap<PlayerId, Cards>>();
+
+            let exchange = Exchange {
+                player: *id,
+                player_card,
+            };
+
+            let all_exchanged = game.submit_exchange(exchange.clone()).unwrap();
+            assert_eq!(all_exchanged, i == ids.len() - 1);
+
+            assert!(game.submit_exchange(exchange).is_err());
+        }
+
+        let received = game.exchange_cards().unwrap();
+
+        for (id, player) in game.players.iter() {
+            let hand = player.hand.as_ref().unwrap();
             assert_eq!(hand.cards.len(), 14);
+
+            let given = hands_before.get(id).unwrap().cards[..3].to_vec();
+            assert!(given.iter().all(|c| !hand.cards.contains(c)));
+
+            let received_cards = received.get(id).unwrap();
+            assert_eq!(received_cards.len(), 3);
+            assert!(!received_cards.contains_key(id));
+            assert!(received_cards.values().all(|c| hand.cards.contains(c)));
         }
     }
 
     #[test]
-    fn test_deal_cards() {
-        let mut game = dummy_game();
-        game.deal_cards();
-        for player in game.players.values() {
+    fn test_exchange_with_same_usernames() {
+        let mut game = Game::new("test_game".to_string(), HashMap::new());
+        for _ in 0..4 {
+            game.join_lobby("bob".to_string(), Sid::new()).unwrap();
+        }
+        deal_all_cards(&mut game);
+        game.phase = Some(Phase::Exchanging);
+
+        let pending = game.auto_exchange().unwrap();
+        assert_eq!(pending.len(), 4);
+
+        let received = game.exchange_cards().unwrap();
+
+        for (id, player) in game.players.iter() {
             assert_eq!(player.hand.as_ref().unwrap().cards.len(), 14);
+            assert_eq!(received[id].len(), 3);
+            assert!(!received[id].contains_key(id));
         }
     }
 
     #[test]
-    fn test_validate_exchange() {
+    fn test_timed_out_decisions() {
         let mut game = dummy_game();
         game.deal_cards();
+        game.phase = Some(Phase::GrandTichu);
 
-        let usernames = ["0", "1", "2", "3"];
-
-        for player in game.players.values() {
-            let valid_usernames = usernames
-                .iter()
-                .filter_map(|u| {
-                    if **u != player.username {
-                        Some(u.to_string())
-                    } else {
-                        None
-                    }
-                })
-                .collect::<Vec<String>>();
+        let ids = game.players.keys().cloned().collect::<Vec<_>>();
+        game.call_grand_tichu(ids[0], true).unwrap();
 
-            let cards = player
-                .hand
-                .clone()
-                .expect("player has no hand")
-                .cards
-                .iter()
-                .take(3)
-                .cloned()
-                .collect::<Vec<_>>();
+        let undecided = game.decline_grand_tichu().unwrap();
+        assert_eq!(undecided.len(), 3);
+        assert!(!undecided.contains(&ids[0]));
+        assert_eq!(game.players[&ids[0]].call, Some(Call::GrandTichu));
+        assert!(game.players.values().all(|p| p.undealt.is_none()));
 
-            let valid_player_card = valid_usernames
-                .iter()
-                .cloned()
-                .zip(cards.iter().cloned())
-                .collect::<HashMap<String, Cards>>();
+        game.phase = Some(Phase::Exchanging);
 
-            let exchange = Exchange {
-                player: player.socket_id,
-                player_card: valid_player_card,
-            };
+        let lowest = game
+            .players
+            .iter()
+            .map(|(id, p)| {
+                let mut cards = p.hand.as_ref().unwrap().cards.clone();
+                cards.sort();
+                (*id, cards[..3].to_vec())
+            })
+            .collect::<HashMap<_, _>>();
+
+        let pending = game.auto_exchange().unwrap();
+        assert_eq!(pending.len(), 4);
+
+        for (id, player) in game.players.iter() {
+            let given = player.exchange.as_ref().unwrap();
+            assert_eq!(given.len(), 3);
+            assert!(given.values().all(|c| lowest[id].contains(c)));
+        }
 
-            let result = game.validate_exchange(&exchange);
-            assert!(result.is_ok());
+        assert!(game.exchange_cards().is_ok());
+    }
 
-            let identical_cards = [cards[0].clone(), cards[0].clone(), cards[0].clone()];
+    #[test]
+    fn test_game_errors() {
+        let mut game = dummy_game();
+        deal_all_cards(&mut game);
 
-            let invalid_player_card = valid_usernames
-                .iter()
-                .cloned()
-                .zip(identical_cards)
-                .collect::<HashMap<String, Cards>>();
+        let not_started = game.play_turn(Turn {
+            player: PlayerId::new_v4(),
+            action: Action::Pass,
+            cards: None,
+        });
+        assert!(matches!(not_started, Err(GameError::WrongPhase(_))));
 
-            let invalid_exchange = Exchange {
-                player: player.socket_id,
-                player_card: invalid_player_card,
-            };
+        game.start().unwrap();
 
-            let result = game.validate_exchange(&invalid_exchange);
-            assert!(result.is_err());
+        let p1 = game.round.as_ref().unwrap().current_player;
+        let p2 = game.round.as_ref().unwrap().prev_next_player[&p1].id;
 
-            let mut invalid_users = valid_usernames.clone();
-            invalid_users[0] = player.username.clone();
-            let invalid_player_card = invalid_users
-                .iter()
-                .cloned()
-                .zip(cards.iter().cloned())
-                .collect::<HashMap<String, Cards>>();
+        let not_your_turn = game.play_turn(Turn {
+            player: p2,
+            action: Action::Pass,
+            cards: None,
+        });
+        assert!(matches!(not_your_turn, Err(GameError::NotYourTurn)));
 
-            let invalid_exchange = Exchange {
-                player: player.socket_id,
-                player_card: invalid_player_card,
-            };
+        let invalid_trick = game.play_turn(Turn {
+            player: p1,
+            action: Action::Play,
+            cards: Some(vec![Cards::Dog, Cards::Dragon]),
+        });
+        assert!(matches!(invalid_trick, Err(GameError::InvalidTrick(_))));
+
+        assert!(matches!(
+            game.join_team(p1, Team::Two),
+            Err(GameError::TeamFull)
+        ));
+        assert!(matches!(
+            game.view_for(PlayerId::new_v4()),
+            Err(GameError::PlayerNotFound)
+        ));
+
+        let json = serde_json::to_value(GameError::NotYourTurn).unwrap();
+        assert_eq!(json["code"], "not_your_turn");
+        assert_eq!(json["message"], "not your turn");
+    }
 
-            let result = game.validate_exchange(&invalid_exchange);
-            assert!(result.is_err());
-        }
+    #[test]
+    fn test_join_team_in_lobby() {
+        let mut game = dummy_game();
+        let player = game.seats()[0].id;
+        let team = game.players[&player].team.clone().unwrap();
+
+        game.join_team(player, Team::Spectator).unwrap();
+        game.join_team(player, team.clone()).unwrap();
+
+        game.begin_round().unwrap();
+        assert!(matches!(
+            game.join_team(player, Team::Spectator),
+            Err(GameError::WrongPhase(_))
+        ));
+        assert_eq!(game.players[&player].team, Some(team));
     }
 
     #[test]
     fn test_turns() {
         let mut game = dummy_game();
-        game.deal_cards();
+        deal_all_cards(&mut game);
         game.start().unwrap();
 
         assert_eq!(game.round.is_some(), true);
@@ -153,7 +1544,7 @@ mod tests {
     #[test]
     fn test_alternating_teams() {
         let mut game = dummy_game();
-        game.deal_cards();
+        deal_all_cards(&mut game);
         game.start().unwrap();
 
         assert_eq!(game.round.is_some(), true);
@@ -164,7 +1555,7 @@ mod tests {
             let prev = previous.clone();
             let curr = current.clone();
             let team_previous = game.players.get(&prev).unwrap().team.clone();
-            let team_current = game.players.get(&curr.socket_id).unwrap().team.clone();
+            let team_current = game.players.get(&curr.id).unwrap().team.clone();
             assert_ne!(team_previous, team_current);
         }
     }
@@ -172,7 +1563,7 @@ mod tests {
     #[test]
     fn test_starting_player() {
         let mut game = dummy_game();
-        game.deal_cards();
+        deal_all_cards(&mut game);
         game.start().unwrap();
 
         assert_eq!(game.round.is_some(), true);
@@ -193,7 +1584,7 @@ mod tests {
         assert_eq!(player_has_mahjong, true);
 
         for player in game.players.values() {
-            if player.socket_id != players_turn {
+            if player.id != players_turn {
                 let player_has_mahjong = player
                     .hand
                     .as_ref()
@@ -459,7 +1850,6 @@ mod tests {
             ),
         ];
         full_house_trick_tests.iter().for_each(|(cards, expected)| {
-            let trick = TrickType::try_from(cards.as_slice());
             assert_eq!(TrickType::try_from(cards.as_slice()).unwrap(), *expected)
         });
     }
@@ -496,6 +1886,161 @@ mod tests {
             .for_each(|cards| assert!(TrickType::try_from(cards.as_slice()).is_err()));
     }
 
+    #[test]
+    fn test_phoenix_wildcard() {
+        let phoenix = || Cards::Phoenix(Box::new(Phoenix { value: None }));
+
+        let phoenix_tests = [
+            (
+                vec![Cards::Seven(Color::Black), phoenix()],
+                TrickType::Pair,
+                7,
+            ),
+            (
+                vec![
+                    Cards::Seven(Color::Black),
+                    Cards::Seven(Color::Red),
+                    phoenix(),
+                ],
+                TrickType::Triple,
+                7,
+            ),
+            (
+                vec![
+                    Cards::King(Color::Black),
+                    Cards::King(Color::Red),
+                    Cards::Two(Color::Black),
+                    Cards::Two(Color::Red),
+                    phoenix(),
+                ],
+                TrickType::FullHouse,
+                13,
+            ),
+            (
+                vec![
+                    Cards::Three(Color::Black),
+                    Cards::Four(Color::Red),
+                    phoenix(),
+                    Cards::Six(Color::Black),
+                    Cards::Seven(Color::Red),
+                ],
+                TrickType::Straight,
+                5,
+            ),
+            (
+                vec![
+                    Cards::Jack(Color::Black),
+                    Cards::Queen(Color::Red),
+                    Cards::King(Color::Black),
+                    Cards::Ace(Color::Red),
+                    phoenix(),
+                ],
+                TrickType::Straight,
+                10,
+            ),
+            (
+                vec![
+                    Cards::Two(Color::Black),
+                    Cards::Two(Color::Red),
+                    Cards::Three(Color::Black),
+                    phoenix(),
+                ],
+                TrickType::SequenceOfPairs,
+                3,
+            ),
+        ];
+
+        phoenix_tests.iter().for_each(|(cards, expected, value)| {
+            assert_eq!(
+                TrickType::resolve(cards.as_slice()).unwrap(),
+                (expected.clone(), Some(*value))
+            );
+
+            let resolved = resolve_phoenix(cards, None).unwrap();
+            assert!(resolved
+                .iter()
+                .any(|c| matches!(c, Cards::Phoenix(p) if p.value == Some(*value))));
+        });
+
+        let invalid_phoenix_tests = [
+            vec![
+                Cards::Three(Color::Black),
+                Cards::Four(Color::Red),
+                phoenix(),
+                Cards::Seven(Color::Red),
+            ],
+            vec![
+                Cards::Mahjong(Box::new(Mahjong { wish: None })),
+                Cards::Phoenix(Box::new(Phoenix { value: Some(1) })),
+            ],
+            vec![Cards::Dragon, phoenix()],
+            vec![
+                Cards::Three(Color::Black),
+                Cards::Four(Color::Black),
+                Cards::Six(Color::Black),
+                Cards::Seven(Color::Black),
+            ],
+        ];
+
+        invalid_phoenix_tests
+            .iter()
+            .for_each(|cards| assert!(TrickType::try_from(cards.as_slice()).is_err()));
+
+        //a single phoenix counts half a rank above the card it is played on
+        let single = resolve_phoenix(&[phoenix()], Some(&[Cards::Ace(Color::Black)])).unwrap();
+        assert!(compare_tricks(&[Cards::Ace(Color::Black)], &single).is_ok());
+        assert!(compare_tricks(&single, &[Cards::Ace(Color::Red)]).is_err());
+        assert!(compare_tricks(&single, &[Cards::Dragon]).is_ok());
+        assert!(compare_tricks(&[Cards::Dragon], &[phoenix()]).is_err());
+
+        let single = resolve_phoenix(&[phoenix()], Some(&[Cards::Two(Color::Black)])).unwrap();
+        assert!(compare_tricks(&single, &[Cards::Two(Color::Red)]).is_err());
+        assert!(compare_tricks(&single, &[Cards::Three(Color::Red)]).is_ok());
+
+        let lead = resolve_phoenix(&[phoenix()], None).unwrap();
+        assert!(compare_tricks(&lead, &[Cards::Two(Color::Red)]).is_ok());
+
+        let straight = [
+            Cards::Three(Color::Black),
+            Cards::Four(Color::Red),
+            Cards::Five(Color::Black),
+            Cards::Six(Color::Black),
+            Cards::Seven(Color::Red),
+        ];
+        let phoenix_straight = [
+            Cards::Four(Color::Black),
+            Cards::Five(Color::Red),
+            phoenix(),
+            Cards::Seven(Color::Black),
+            Cards::Eight(Color::Red),
+        ];
+        assert!(compare_tricks(&straight, &phoenix_straight).is_ok());
+    }
+
+    #[test]
+    fn test_phoenix_rank_does_not_fit() {
+        let phoenix = |value| Cards::Phoenix(Box::new(Phoenix { value }));
+
+        let pair = [Cards::Five(Color::Black), phoenix(None)];
+        assert_eq!(
+            TrickType::resolve(&pair).unwrap(),
+            (TrickType::Pair, Some(5))
+        );
+
+        let pair = [Cards::Five(Color::Black), phoenix(Some(5))];
+        assert_eq!(
+            TrickType::resolve(&pair).unwrap(),
+            (TrickType::Pair, Some(5))
+        );
+
+        //no other rank is picked in its place
+        let pair = [Cards::Five(Color::Black), phoenix(Some(7))];
+        assert!(matches!(
+            TrickType::resolve(&pair),
+            Err(GameError::InvalidTrick(_))
+        ));
+    }
+
     #[test]
     fn test_straight() {
         let straight_trick_tests = vec![
@@ -1059,6 +2604,128 @@ mod tests {
             });
     }
 
+    #[test]
+    fn test_legal_plays() {
+        let phoenix = || Cards::Phoenix(Box::new(Phoenix { value: None }));
+        let hand = Hand {
+            cards: vec![
+                Cards::Five(Color::Black),
+                Cards::Five(Color::Red),
+                Cards::Six(Color::Black),
+                Cards::Seven(Color::Red),
+                Cards::Eight(Color::Blue),
+                phoenix(),
+                Cards::Dog,
+            ],
+        };
+
+        //7 singles, 6 pairs, a triple, 4 straights and a sequence of pairs
+        let plays = legal_plays(&hand, None, None);
+        assert_eq!(plays.len(), 19);
+        assert!(plays.contains(&vec![Cards::Dog]));
+
+        //the phoenix is listed for both ends of the straight
+        let straights = plays
+            .iter()
+            .filter(|cards| cards.len() == 5)
+            .filter_map(|cards| {
+                cards.iter().find_map(|c| {
+                    c.get_card_number()
+                        .filter(|_| matches!(c, Cards::Phoenix(_)))
+                })
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(straights, vec![4, 4, 9, 9]);
+
+        let pair = [Cards::Six(Color::Red), Cards::Six(Color::Green)];
+        let plays = legal_plays(&hand, Some(&pair), None);
+        assert_eq!(plays.len(), 2);
+        assert!(plays
+            .iter()
+            .all(|cards| compare_tricks(&pair, cards).is_ok()));
+
+        let straight = [
+            Cards::Two(Color::Red),
+            Cards::Three(Color::Green),
+            Cards::Four(Color::Red),
+            Cards::Five(Color::Green),
+            Cards::Six(Color::Red),
+        ];
+        assert_eq!(legal_plays(&hand, Some(&straight), None).len(), 4);
+        assert!(legal_plays(&hand, Some(&[Cards::Dragon]), None).is_empty());
+
+        //a wish that can be fulfilled leaves only the plays fulfilling it
+        let plays = legal_plays(&hand, None, Some(8));
+        assert_eq!(plays.len(), 6);
+        assert!(plays
+            .iter()
+            .all(|cards| cards.contains(&Cards::Eight(Color::Blue))));
+        assert_eq!(
+            legal_plays(&hand, Some(&pair), Some(8)),
+            vec![vec![Cards::Eight(Color::Blue), phoenix()]]
+        );
+        assert_eq!(legal_plays(&hand, None, Some(2)).len(), 19);
+    }
+
+    #[test]
+    fn test_legal_plays_match_compare_tricks() {
+        fn same_cards(a: &[Cards], b: &[Cards]) -> bool {
+            a.len() == b.len() && a.iter().all(|c| b.contains(c))
+        }
+
+        for _ in 0..50 {
+            let hands = generate_hands();
+            let hand = Hand {
+                cards: hands[0].cards[..9].to_vec(),
+            };
+            let other = Hand {
+                cards: hands[1].cards[..9].to_vec(),
+            };
+
+            let mut last_tricks = legal_plays(&other, None, None)
+                .into_iter()
+                .filter(|cards| cards[0] != Cards::Dog)
+                .map(Some)
+                .collect::<Vec<_>>();
+            last_tricks.push(None);
+
+            for last_trick in last_tricks {
+                let last_trick = last_trick.as_deref();
+                let plays = legal_plays(&hand, last_trick, None);
+
+                for cards in plays.iter() {
+                    assert!(cards.iter().all(|c| hand.cards.contains(c)));
+                    if let Some(last_trick) = last_trick {
+                        assert!(compare_tricks(last_trick, cards).is_ok());
+                    }
+                }
+
+                //every subset of the hand the rules accept is found
+                for selection in 1u32..1 << hand.cards.len() {
+                    let cards = (0..hand.cards.len())
+                        .filter(|i| selection & (1 << i) != 0)
+                        .map(|i| hand.cards[i].clone())
+                        .collect::<Vec<_>>();
+
+                    let legal = resolve_phoenix(&cards, last_trick).is_ok_and(|cards| {
+                        last_trick
+                            .map_or(cards[0] != Cards::Dog || cards.len() == 1, |last_trick| {
+                                compare_tricks(last_trick, &cards).is_ok()
+                            })
+                    });
+
+                    assert_eq!(
+                        legal,
+                        plays.iter().any(|play| same_cards(play, &cards)),
+                        "{:?} on {:?}",
+                        cards,
+                        last_trick
+                    );
+                }
+            }
+        }
+    }
+
     #[test]
     fn test_bombs() {
         let bomb_trick_tests = vec![
@@ -1148,7 +2815,7 @@ mod tests {
     #[test]
     fn test_init_round() {
         let mut game = dummy_game();
-        game.deal_cards();
+        deal_all_cards(&mut game);
         game.start().unwrap();
 
         let first_player = game.round.as_ref().unwrap().current_player;
@@ -1160,7 +2827,13 @@ mod tests {
             .clone()
             .unwrap();
 
-        let first_player_card = first_player_hand.cards.first().unwrap().clone();
+        //the dog hands the lead on instead of starting a trick
+        let first_player_card = first_player_hand
+            .cards
+            .iter()
+            .find(|c| **c != Cards::Dog)
+            .unwrap()
+            .clone();
 
         let turn = Turn {
             player: first_player,
@@ -1189,7 +2862,7 @@ mod tests {
                 .prev_next_player
                 .get(&first_player)
                 .unwrap()
-                .socket_id,
+                .id,
             next_player
         );
 
@@ -1199,10 +2872,42 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_init_round_with_dog() {
+        let mut game = dummy_game();
+        deal_all_cards(&mut game);
+        game.start().unwrap();
+
+        let first_player = game.round.as_ref().unwrap().current_player;
+        let partner = game.partner(first_player).unwrap();
+        game.players.get_mut(&first_player).unwrap().hand = Some(Hand {
+            cards: vec![Cards::Dog, Cards::Two(Color::Black)],
+        });
+
+        let turn = Turn {
+            player: first_player,
+            action: Action::Play,
+            cards: Some(vec![Cards::Dog]),
+        };
+
+        assert_eq!(game.play_turn(turn).unwrap(), false);
+
+        let round = game.round.as_ref().unwrap();
+        assert_eq!(round.current_player, partner);
+        assert_eq!(round.last_played_player, partner);
+        assert_eq!(round.previous_action, None);
+        assert!(round.current_trick.is_empty());
+        assert_eq!(round.current_trick_type, None);
+        assert_eq!(
+            game.players[&first_player].hand.as_ref().unwrap().cards,
+            vec![Cards::Two(Color::Black)]
+        );
+    }
+
     #[test]
     fn test_invalid_init_round() {
         let mut game = dummy_game();
-        game.deal_cards();
+        deal_all_cards(&mut game);
         game.start().unwrap();
 
         let second_player = game
@@ -1216,7 +2921,7 @@ mod tests {
 
         let second_player_hand = game
             .players
-            .get(&second_player.socket_id)
+            .get(&second_player.id)
             .unwrap()
             .hand
             .clone()
@@ -1225,19 +2930,28 @@ mod tests {
         let second_player_card = second_player_hand.cards.first().unwrap().clone();
 
         let turn = Turn {
-            player: second_player.socket_id,
+            player: second_player.id,
             action: Action::Play,
             cards: Some(vec![second_player_card]),
         };
 
         assert_eq!(game.play_turn(turn).is_err(), true);
+
+        //the leader has to open the trick
+        let pass = Turn {
+            player: game.round.as_ref().unwrap().current_player,
+            action: Action::Pass,
+            cards: None,
+        };
+
+        assert!(game.play_turn(pass).is_err());
     }
 
     #[test]
     fn test_play_turns() {
         let mut game = dummy_game();
 
-        game.deal_cards();
+        deal_all_cards(&mut game);
         game.start().unwrap();
 
         let all_cards = game.players.values().fold(vec![], |mut acc, player| {
@@ -1312,8 +3026,6 @@ mod tests {
         assert_eq!(result.is_ok(), true);
         assert_eq!(result.unwrap(), true);
 
-        assert_eq!(game.cleanup_trick().is_ok(), true);
-
         //turn is over, next player should be the winner of the last trick
         let next_player = game.round.as_ref().unwrap().current_player;
 
@@ -1396,8 +3108,6 @@ mod tests {
         assert_eq!(result.is_ok(), true);
         assert_eq!(result.unwrap(), true);
 
-        assert_eq!(game.cleanup_trick().is_ok(), true);
-
         assert_eq!(game.round.as_ref().unwrap().current_player, p1);
 
         assert_eq!(game.players.get(&p1).unwrap().trick_points, 10);
@@ -1533,8 +3243,6 @@ mod tests {
         assert_eq!(result.is_ok(), true);
         assert_eq!(result.unwrap(), true);
 
-        assert_eq!(game.cleanup_trick().is_ok(), true);
-
         assert_eq!(game.round.unwrap().current_player, p4);
 
         assert_eq!(game.players.get(&p1).unwrap().trick_points, 10);
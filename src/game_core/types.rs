@@ -1,23 +1,106 @@
-use anyhow::anyhow;
 use std::collections::HashMap;
 
 use serde::{Deserialize, Serialize};
 use socketioxide::socket::Sid;
+use uuid::Uuid;
+
+use crate::game_core::error::{GameError, GameResult};
+
+/// Stays the same for a player across reconnects, unlike the socket id.
+pub type PlayerId = Uuid;
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct Round {
-    pub prev_next_player: HashMap<Sid, Player>,
-    pub current_player: Sid,
-    pub last_played_player: Sid,
+    pub prev_next_player: HashMap<PlayerId, Player>,
+    pub current_player: PlayerId,
+    pub last_played_player: PlayerId,
     pub previous_action: Option<Action>,
     pub current_trick: Vec<Vec<Cards>>,
     pub current_trick_type: Option<TrickType>,
-    pub first_to_finish: Option<Sid>,
+    pub first_to_finish: Option<PlayerId>,
+    pub double_victory: Option<Team>,
+    pub dragon_gift: Option<PlayerId>,
+    pub wish: Option<u8>,
+    pub summary: Option<RoundSummary>,
+    pub trick_won: Option<TrickWon>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TrickWon {
+    pub winner: PlayerId,
+    pub points: i8,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RoundSummary {
+    pub points_t1: i16,
+    pub points_t2: i16,
+    pub score_t1: i16,
+    pub score_t2: i16,
+}
+
+/// Sent once the game is over, with the seed to replay its deals.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct GameSummary {
+    pub winner: Team,
+    pub score_t1: i16,
+    pub score_t2: i16,
+    pub seed: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrickState {
+    pub trick: Vec<Vec<Cards>>,
+    pub trick_type: Option<TrickType>,
+    pub wish: Option<u8>,
+}
+
+/// The game as seen by a single player, without the cards of the other players.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GameView {
+    pub game_id: String,
+    pub phase: Option<Phase>,
+    pub score_t1: i16,
+    pub score_t2: i16,
+    pub hand: Option<Hand>,
+    pub players: Vec<PlayerView>,
+    pub current_player: Option<PlayerId>,
+    pub trick: Vec<Vec<Cards>>,
+    pub trick_type: Option<TrickType>,
+    pub wish: Option<u8>,
+    pub dragon_gift: Option<PlayerId>,
+    pub winner: Option<Team>,
+    pub config: GameConfig,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlayerView {
+    pub id: PlayerId,
+    pub name: String,
+    pub is_host: bool,
+    pub team: Option<Team>,
+    pub place: u8,
+    pub card_count: usize,
+    pub call: Option<Call>,
+    pub away: bool,
+    pub bot: bool,
+}
+
+/// The strategy a bot plays with.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum BotKind {
+    Rules,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Turn {
-    pub player: Sid,
+    pub player: PlayerId,
     pub action: Action,
     pub cards: Option<Vec<Cards>>,
 }
@@ -28,42 +111,178 @@ pub enum Action {
     Play,
 }
 
-pub fn generate_player_turn_sequence(players: Vec<Player>) -> HashMap<Sid, Player> {
+pub fn generate_player_turn_sequence(
+    players: Vec<Player>,
+) -> GameResult<HashMap<PlayerId, Player>> {
     let mut turn_sequence = HashMap::new();
-    let mut previous_player = players.last().unwrap().clone();
+    let mut previous_player = players.last().ok_or(GameError::InvalidTeams)?.clone();
     for current_player in players.iter() {
-        turn_sequence.insert(previous_player.socket_id, current_player.clone());
+        turn_sequence.insert(previous_player.id, current_player.clone());
         previous_player = current_player.to_owned();
     }
-    turn_sequence
+    Ok(turn_sequence)
 }
 
-impl Iterator for Round {
-    type Item = Sid;
+impl Round {
+    pub fn mark_finished(&mut self, player: PlayerId) {
+        self.prev_next_player
+            .values_mut()
+            .filter(|p| p.id == player)
+            .for_each(|p| p.hand = None);
 
-    fn next(&mut self) -> Option<Self::Item> {
-        let mut next_player = self.prev_next_player.get(&self.current_player);
+        if self.first_to_finish.is_none() {
+            self.first_to_finish = Some(player);
+        }
+    }
+
+    /// Clears the wish once a card of the wished rank is played and remembers a new wish.
+    pub fn update_wish(&mut self, trick: &[Cards], wish: Option<u8>) {
+        if let Some(current_wish) = self.wish {
+            if trick.iter().any(|c| c.has_rank(current_wish)) {
+                self.wish = None;
+            }
+        }
+
+        if wish.is_some() {
+            self.wish = wish;
+        }
+    }
+
+    pub fn trick_state(&self) -> TrickState {
+        TrickState {
+            trick: self.current_trick.clone(),
+            trick_type: self.current_trick_type.clone(),
+            wish: self.wish,
+        }
+    }
+
+    /// Hands the lead to the given player, or to the next player after them who still has cards.
+    pub fn jump_to(&mut self, player: PlayerId) -> Option<PlayerId> {
+        let mut next_player = self.prev_next_player.values().find(|p| p.id == player)?;
 
-        //if a player has no hand, skip him
-        if next_player.unwrap().hand.is_none() {
-            next_player = self.prev_next_player.get(&next_player.unwrap().socket_id);
+        while next_player.hand.is_none() {
+            next_player = self.prev_next_player.get(&next_player.id)?;
         }
 
-        if let Some(prev_action) = &self.previous_action {
-            if prev_action == &Action::Pass
-                && next_player.unwrap().socket_id == self.last_played_player
+        self.current_player = next_player.id;
+        self.last_played_player = next_player.id;
+        self.previous_action = None;
+        Some(self.current_player)
+    }
+}
+
+impl Iterator for Round {
+    type Item = PlayerId;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut next_player = self.prev_next_player.get(&self.current_player)?;
+
+        loop {
+            //the trick is over once everyone after the last player passed, even if that player
+            //has no cards left
+            if self.previous_action == Some(Action::Pass)
+                && next_player.id == self.last_played_player
             {
                 self.current_player = self.last_played_player;
                 return None;
             }
+
+            //if a player has no hand, skip him
+            if next_player.hand.is_some() {
+                break;
+            }
+            next_player = self.prev_next_player.get(&next_player.id)?;
         }
-        self.current_player = next_player.unwrap().socket_id;
+
+        self.current_player = next_player.id;
         Some(self.current_player)
     }
 }
 
+/// Chosen by the host when creating the lobby.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct GameConfig {
+    /// The game ends once a team reached this score.
+    pub target_score: i16,
+    pub timeouts: Timeouts,
+    /// Without Grand Tichu all fourteen cards are dealt at once.
+    pub grand_tichu: bool,
+    pub exchange: bool,
+    pub house_rules: HouseRules,
+    /// Deals the same cards for the same seed. It is kept from the players until the game is
+    /// over, as it gives away every hand.
+    #[serde(skip_serializing)]
+    pub seed: Option<u64>,
+}
+
+impl Default for GameConfig {
+    fn default() -> Self {
+        GameConfig {
+            target_score: 1000,
+            timeouts: Timeouts::default(),
+            grand_tichu: true,
+            exchange: true,
+            house_rules: HouseRules::default(),
+            seed: None,
+        }
+    }
+}
+
+impl GameConfig {
+    pub fn validate(&self) -> GameResult<()> {
+        if self.target_score <= 0 {
+            return Err(GameError::InvalidConfig(
+                "the target score must be positive".into(),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+/// Rules tables commonly play without, all enabled by default.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct HouseRules {
+    /// Bombs may interrupt the turn order.
+    pub bombs_out_of_turn: bool,
+    /// A team finishing first and second ends the round with 200 points.
+    pub double_victory: bool,
+}
+
+impl Default for HouseRules {
+    fn default() -> Self {
+        HouseRules {
+            bombs_out_of_turn: true,
+            double_victory: true,
+        }
+    }
+}
+
+/// Seconds a player has for an action before it is taken for them, zero disables the limit.
+/// Grand Tichu and the exchange are timed for the whole phase, turns one by one.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Timeouts {
+    pub grand_tichu: u64,
+    pub exchange: u64,
+    pub turn: u64,
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        Timeouts {
+            grand_tichu: 30,
+            exchange: 60,
+            turn: 30,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Phase {
+    GrandTichu,
     Exchanging,
     Playing,
 }
@@ -71,8 +290,12 @@ pub enum Phase {
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct Player {
-    #[serde(rename = "id")]
+    pub id: PlayerId,
+    //the socket changes whenever the player reconnects
+    #[serde(skip)]
     pub socket_id: Sid,
+    #[serde(skip)]
+    pub token: Uuid,
     #[serde(rename = "name")]
     pub username: String,
     pub is_host: bool,
@@ -81,9 +304,57 @@ pub struct Player {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub team: Option<Team>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub exchange: Option<HashMap<String, Cards>>,
+    pub exchange: Option<HashMap<PlayerId, Cards>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub undealt: Option<Vec<Cards>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub call: Option<Call>,
+    pub away: bool,
     pub trick_points: i8,
     pub place: u8,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bot: Option<BotKind>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Call {
+    GrandTichu,
+    Tichu,
+}
+
+impl Player {
+    pub fn view(&self) -> PlayerView {
+        PlayerView {
+            id: self.id,
+            name: self.username.clone(),
+            is_host: self.is_host,
+            team: self.team.clone(),
+            place: self.place,
+            card_count: self.hand.as_ref().map_or(0, |h| h.cards.len()),
+            call: self.call.clone(),
+            away: self.away,
+            bot: self.bot.is_some(),
+        }
+    }
+
+    /// Takes away everything a player gathered during a round.
+    pub fn reset(&mut self) {
+        self.hand = None;
+        self.exchange = None;
+        self.undealt = None;
+        self.call = None;
+        self.trick_points = 0;
+    }
+}
+
+impl Call {
+    pub fn get_points(&self) -> i16 {
+        match self {
+            Call::GrandTichu => 200,
+            Call::Tichu => 100,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -112,11 +383,30 @@ pub enum Cards {
     Dragon,
 }
 
-#[derive(Debug, Clone, PartialEq, Ord, PartialOrd, Eq, Deserialize, Serialize)]
+#[derive(Debug, Clone, Eq, Deserialize, Serialize)]
 pub struct Mahjong {
     pub wish: Option<Cards>,
 }
 
+//the wish is not part of the card itself
+impl PartialEq for Mahjong {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+impl PartialOrd for Mahjong {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Mahjong {
+    fn cmp(&self, _: &Self) -> std::cmp::Ordering {
+        std::cmp::Ordering::Equal
+    }
+}
+
 #[derive(Debug, Clone, Ord, Eq, PartialOrd, Deserialize, Serialize)]
 pub struct Phoenix {
     pub value: Option<u8>,
@@ -190,6 +480,11 @@ impl Cards {
             _ => None,
         }
     }
+    /// Checks the printed rank of a card, a phoenix standing in for a rank does not count.
+    pub fn has_rank(&self, rank: u8) -> bool {
+        !matches!(self, Cards::Phoenix(_)) && self.get_card_number() == Some(rank)
+    }
+
     fn get_color(&self) -> Option<Color> {
         match self {
             Cards::Two(c) => Some(c.clone()),
@@ -221,110 +516,147 @@ impl Cards {
     }
 }
 
+impl TrickType {
+    pub fn is_bomb(&self) -> bool {
+        matches!(self, TrickType::FourOfAKind | TrickType::StraightFlush)
+    }
+}
+
 impl TryFrom<&[Cards]> for TrickType {
-    type Error = anyhow::Error;
+    type Error = GameError;
 
-    fn try_from(cards: &[Cards]) -> anyhow::Result<Self> {
-        fn all_equal(cards: &[Cards]) -> bool {
-            let mut card_types = cards
-                .iter()
-                .filter_map(|c| c.get_card_number())
-                .collect::<Vec<u8>>();
-            card_types.sort();
-            card_types.dedup();
-            card_types.len() == 1
-        }
+    fn try_from(cards: &[Cards]) -> GameResult<Self> {
+        TrickType::resolve(cards).map(|(trick_type, _)| trick_type)
+    }
+}
 
-        fn is_sequence(cards: &[Cards]) -> bool {
-            let mut card_digits = cards
-                .iter()
-                .filter_map(|c| c.get_card_number())
-                .collect::<Vec<u8>>();
-            card_digits.sort();
-            card_digits.windows(2).all(|w| w[0] + 1 == w[1])
+impl TrickType {
+    /// Classifies a trick and returns the rank the phoenix stands for within a combination.
+    /// Without a rank sent along, the phoenix stands for the highest rank that fits the trick. A
+    /// rank that does not fit makes the trick invalid. A single phoenix has no rank of its own.
+    pub fn resolve(cards: &[Cards]) -> GameResult<(Self, Option<u8>)> {
+        fn all_equal(ranks: &[u8]) -> bool {
+            ranks.windows(2).all(|w| w[0] == w[1])
         }
 
-        fn is_sequence_of_pairs(cards: &[Cards]) -> bool {
-            let mut card_digits = cards
-                .iter()
-                .filter_map(|c| c.get_card_number())
-                .collect::<Vec<u8>>();
-            card_digits.sort();
-            card_digits
-                .windows(4)
-                .all(|w| w[0] == w[1] && w[2] == w[3] && w[0] + 1 == w[2])
+        fn is_sequence(ranks: &[u8]) -> bool {
+            ranks.windows(2).all(|w| w[0] + 1 == w[1])
         }
 
-        fn is_full_house(cards: &[Cards]) -> bool {
-            let card_values = cards
-                .iter()
-                .filter_map(|c| c.get_card_number())
-                .collect::<Vec<u8>>();
+        fn is_sequence_of_pairs(ranks: &[u8]) -> bool {
+            let pair_ranks = ranks.iter().step_by(2).cloned().collect::<Vec<_>>();
 
-            let mut unique_cards = card_values.clone();
-            unique_cards.sort();
-            unique_cards.dedup();
+            ranks.len().is_multiple_of(2)
+                && ranks.chunks(2).all(|pair| pair[0] == pair[1])
+                && is_sequence(&pair_ranks)
+        }
+
+        fn is_full_house(ranks: &[u8]) -> bool {
+            //ranks are sorted, so either the triple or the pair comes first
+            (ranks[0] == ranks[2] && ranks[3] == ranks[4] && ranks[2] != ranks[3])
+                || (ranks[0] == ranks[1] && ranks[2] == ranks[4] && ranks[1] != ranks[2])
+        }
 
-            if unique_cards.len() != 2 {
-                return false;
+        //expects the ranks to be sorted
+        fn classify(ranks: &[u8]) -> Option<TrickType> {
+            match ranks.len() {
+                2 if all_equal(ranks) => Some(TrickType::Pair),
+                3 if all_equal(ranks) => Some(TrickType::Triple),
+                5 if is_full_house(ranks) => Some(TrickType::FullHouse),
+                4..=14 if is_sequence_of_pairs(ranks) => Some(TrickType::SequenceOfPairs),
+                5..=14 if is_sequence(ranks) => Some(TrickType::Straight),
+                _ => None,
             }
+        }
 
-            let occurrences = unique_cards
+        //the dog and the dragon can only be played on their own
+        if cards.len() > 1
+            && cards
                 .iter()
-                .map(|c| card_values.iter().filter(|&x| x == c).count())
-                .collect::<Vec<_>>();
+                .any(|c| matches!(c, Cards::Dog | Cards::Dragon))
+        {
+            return Err(GameError::InvalidTrick("invalid trick".into()));
+        }
 
-            matches!(occurrences.as_slice(), [2, 3] | [3, 2])
+        if cards.len() == 1 {
+            return Ok((TrickType::Single, None));
         }
 
-        match cards.len() {
-            1 => Ok(TrickType::Single),
-            2 if all_equal(cards) => Ok(TrickType::Pair),
-            3 if all_equal(cards) => Ok(TrickType::Triple),
-            4 => {
-                if cards
-                    .iter()
-                    .all(|c| std::mem::discriminant(c) == std::mem::discriminant(&cards[0]))
-                {
-                    Ok(TrickType::FourOfAKind)
-                } else {
-                    Err(anyhow!("invalid trick"))
-                }
+        let mut ranks = cards
+            .iter()
+            .filter(|c| !matches!(c, Cards::Phoenix(_)))
+            .filter_map(|c| c.get_card_number())
+            .collect::<Vec<u8>>();
+        ranks.sort();
+
+        let phoenix = cards.iter().find_map(|c| match c {
+            Cards::Phoenix(phoenix) => Some(phoenix.value),
+            _ => None,
+        });
+
+        let phoenix_value = match phoenix {
+            None => None,
+            Some(value) if ranks.len() + 1 == cards.len() => value,
+            //there is only one phoenix
+            Some(_) => return Err(GameError::InvalidTrick("invalid trick".into())),
+        };
+
+        if phoenix.is_none() {
+            if cards.len() == 4 && all_equal(&ranks) {
+                return Ok((TrickType::FourOfAKind, None));
             }
-            5 if is_full_house(cards) => Ok(TrickType::FullHouse),
-            4..=14 if is_sequence_of_pairs(cards) => Ok(TrickType::SequenceOfPairs),
-            5..=14 if is_sequence(cards) => {
-                let colors = cards
-                    .iter()
-                    .filter_map(|c| c.get_color())
-                    .collect::<Vec<_>>();
-
-                //creating a straight flush with phoenix is not allowed
-                if colors.len() != cards.len() {
-                    return Ok(TrickType::Straight);
-                }
-                if colors
-                    .iter()
-                    .all(|c| std::mem::discriminant(c) == std::mem::discriminant(&colors[0]))
-                {
-                    return Ok(TrickType::StraightFlush);
+
+            return match classify(&ranks) {
+                Some(TrickType::Straight) => {
+                    let colors = cards
+                        .iter()
+                        .filter_map(|c| c.get_color())
+                        .collect::<Vec<_>>();
+
+                    //the mahjong has no color, so it can't be part of a straight flush
+                    if colors.len() == cards.len()
+                        && colors.iter().all(|c| {
+                            std::mem::discriminant(c) == std::mem::discriminant(&colors[0])
+                        })
+                    {
+                        return Ok((TrickType::StraightFlush, None));
+                    }
+                    Ok((TrickType::Straight, None))
                 }
-                Ok(TrickType::Straight)
-            }
-            _ => Err(anyhow!("invalid trick")),
+                Some(trick_type) => Ok((trick_type, None)),
+                None => Err(GameError::InvalidTrick("invalid trick".into())),
+            };
         }
+
+        //the phoenix can stand for any rank from two to ace, but never completes a bomb
+        let candidates = match phoenix_value {
+            Some(value @ 2..=14) => vec![value],
+            Some(_) => return Err(GameError::InvalidTrick("invalid trick".into())),
+            None => (2..=14).rev().collect(),
+        };
+
+        candidates
+            .into_iter()
+            .find_map(|value| {
+                let mut ranks = ranks.clone();
+                ranks.push(value);
+                ranks.sort();
+                classify(&ranks).map(|trick_type| (trick_type, Some(value)))
+            })
+            .ok_or_else(|| GameError::InvalidTrick("invalid trick".into()))
     }
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Exchange {
-    pub player: Sid,
-    pub player_card: HashMap<String, Cards>,
+    pub player: PlayerId,
+    pub player_card: HashMap<PlayerId, Cards>,
 }
 
 #[cfg(test)]
+#[allow(clippy::bool_assert_comparison, clippy::useless_vec)]
 mod tests {
-    use crate::game_core::core::{Cards, Color, Phoenix};
+    use crate::game_core::core::{Cards, Color, Mahjong, Phoenix};
 
     #[test]
     fn test_partial_eq_phoenix() {
@@ -345,4 +677,15 @@ mod tests {
         assert_eq!(cards.contains(&phoenix2), true);
         assert_eq!(phoenix, phoenix2);
     }
+
+    #[test]
+    fn test_ord_mahjong() {
+        let mahjong = Mahjong { wish: None };
+        let wished = Mahjong {
+            wish: Some(Cards::Ace(Color::Red)),
+        };
+
+        assert_eq!(mahjong.cmp(&wished), std::cmp::Ordering::Equal);
+        assert_eq!(mahjong, wished);
+    }
 }
@@ -1,134 +1,173 @@
-use std::task::Wake;
-
-use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use axum::{
+    extract::{Path, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
 use tracing::info;
+use uuid::Uuid;
 
 use crate::{
-    game_core::core::{Game, Phase, Team},
+    game_client::client::phase_changed,
+    game_core::{
+        core::{Game, GameError, GameResult, Team},
+        log,
+    },
     AppState,
 };
 
+impl IntoResponse for GameError {
+    fn into_response(self) -> Response {
+        let status = match self {
+            GameError::NotFound | GameError::PlayerNotFound => StatusCode::NOT_FOUND,
+            GameError::InvalidToken => StatusCode::UNAUTHORIZED,
+            GameError::NotHost => StatusCode::FORBIDDEN,
+            GameError::TeamFull | GameError::PlayersAway => StatusCode::CONFLICT,
+            GameError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            _ => StatusCode::BAD_REQUEST,
+        };
+
+        (status, Json(self)).into_response()
+    }
+}
+
 #[derive(serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct StartGameBody {
     game_id: String,
+    //the session token of the host
+    token: Uuid,
 }
-pub(crate) async fn start_game(
+pub async fn start_game(
     app_state: State<AppState>,
-    Json(game_id): Json<StartGameBody>,
+    Json(body): Json<StartGameBody>,
 ) -> impl IntoResponse {
-    let game_store = app_state.game_store.clone();
-
-    let mut guard = game_store.lock().unwrap();
-    let game = guard
-        .get_mut(&game_id.game_id)
-        .expect("Game should exist at this stage");
+    let game = match app_state.games.get(&body.game_id) {
+        Ok(game) => game,
+        Err(err) => return err.into_response(),
+    };
 
-    if !validate_teams(game) {
-        return (StatusCode::BAD_REQUEST, "Invalid teams").into_response();
-    }
+    let started = game
+        .call(move |game, io| {
+            let host = game.player_by_token(body.token)?;
+            if !game.players[&host].is_host {
+                return Err(GameError::NotHost);
+            }
 
-    game.deal_cards();
+            if !validate_teams(game) {
+                return Err(GameError::InvalidTeams);
+            }
 
-    let io = app_state.io.clone();
+            if game.players.values().any(|p| p.away) {
+                return Err(GameError::PlayersAway);
+            }
 
-    game.players
-        .values()
-        .for_each(|player| match io.get_socket(player.socket_id) {
-            Some(socket) => {
-                socket.emit("hand", player.hand.clone().unwrap()).unwrap();
+            if game.phase.is_some() {
+                return Err(GameError::WrongPhase("game already started".into()));
             }
-            None => {
-                //TODO: what to do here?
-                panic!("socket not found");
+
+            let phase = game.begin_round()?;
+
+            for player in game.players.values() {
+                match io.get_socket(player.socket_id) {
+                    Some(socket) => {
+                        socket.emit("hand", player.hand.clone()).ok();
+                    }
+                    None => {
+                        info!("socket of player {} not found", player.username);
+                    }
+                }
             }
-        });
-
-    if game.phase.is_none() {
-        let phase = Phase::Exchanging;
-        game.phase = Some(phase.clone());
-        io.to(game_id.game_id.clone())
-            .emit("game-phase", phase)
-            .unwrap();
-    }
-    drop(guard);
 
-    //start_none_blocking_exchange_loop(game_id.game_id.to_string(), app_state.clone());
-    skip_exchange(game_id.game_id.to_string(), app_state.clone());
+            phase_changed(io, game, phase)
+        })
+        .await;
 
-    (StatusCode::OK, "Game started").into_response()
+    match started {
+        Ok(()) => (StatusCode::OK, "Game started").into_response(),
+        Err(err) => err.into_response(),
+    }
 }
 
-fn skip_exchange(game_id: String, app_state: State<AppState>) {
-    let game_store = app_state.game_store.clone();
-    let mut guard = game_store.lock().unwrap();
-    let game = guard.get_mut(&game_id).unwrap();
-    game.start().expect("Game should start");
-
-    let io = app_state.io.clone();
-    let phase = Phase::Playing;
-
-    let player_turn = game.round.as_ref().unwrap().current_player;
+/// The game as seen by the player, who sends their session token as `Authorization: Bearer`.
+pub async fn get_state(
+    app_state: State<AppState>,
+    Path(game_id): Path<String>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let token = match bearer_token(&headers) {
+        Ok(token) => token,
+        Err(err) => return err.into_response(),
+    };
 
-    let player_position = game
-        .players
-        .values()
-        .find(|p| p.socket_id == player_turn)
-        .unwrap()
-        .place;
+    let game = match app_state.games.get(&game_id) {
+        Ok(game) => game,
+        Err(err) => return err.into_response(),
+    };
 
-    game.phase = Some(phase.clone());
+    let view = game
+        .call(move |game, _| {
+            let player_id = game.player_by_token(token)?;
+            game.view_for(player_id)
+        })
+        .await;
 
-    io.to(game_id.clone()).emit("game-phase", phase).unwrap();
-    io.to(game_id.clone()).emit("started", "").unwrap();
-    io.to(game_id).emit("next-player", player_position).unwrap();
+    match view {
+        Ok(view) => (StatusCode::OK, Json(view)).into_response(),
+        Err(err) => err.into_response(),
+    }
 }
 
-//TODO: refactor this nonsense
-fn start_none_blocking_exchange_loop(game_id: String, app_state: State<AppState>) {
-    let mut max_time = 2;
-    let game_store = app_state.game_store.clone();
-    std::thread::spawn(move || loop {
-        info!("waiting for players to exchange");
-        std::thread::sleep(std::time::Duration::from_secs(1));
-
-        max_time -= 1;
-
-        if max_time == 0 {
-            let io = app_state.io.clone();
-            io.to(game_id).emit("disconnect", "timeout").unwrap();
-            break;
+/// The event log of a finished game as JSON Lines.
+pub async fn get_log(app_state: State<AppState>, Path(game_id): Path<String>) -> impl IntoResponse {
+    //the log gives away every hand, so it is only handed out once the game is over
+    let lines = match app_state.games.get(&game_id) {
+        Ok(game) => {
+            game.call(|game, _| {
+                if game.winner.is_none() {
+                    return Err(GameError::WrongPhase("game is not over yet".into()));
+                }
+                log::to_json_lines(&game.log)
+            })
+            .await
         }
+        Err(err) => Err(err),
+    };
 
-        let players = {
-            let guard = game_store.lock().unwrap();
-            let game = guard.get(&game_id).unwrap();
-            game.players.clone()
-        };
-
-        if players.values().all(|p| p.exchange.is_some()) {
-            let mut guard = game_store.lock().unwrap();
-            let game = guard.get_mut(&game_id).unwrap();
-
-            game.start().expect("Game should start");
-
-            let io = app_state.io.clone();
-            let phase = Phase::Playing;
-
-            let player_turn = game.round.as_ref().unwrap().current_player;
-
-            game.phase = Some(phase.clone());
+    //the task of a finished game stops after a while and leaves its log behind
+    let lines = match lines {
+        Err(GameError::NotFound) => app_state
+            .games
+            .finished_log(&game_id)
+            .and_then(|events| log::to_json_lines(&events)),
+        lines => lines,
+    };
 
-            io.to(game_id.clone()).emit("game-phase", phase).unwrap();
-            info!(
-                "username: {:?}",
-                players.get(&player_turn).unwrap().username
-            );
-            io.to(game_id).emit("next-player", player_turn).unwrap();
-            break;
+    match lines {
+        Ok(lines) => {
+            let headers = [
+                (header::CONTENT_TYPE, "application/x-ndjson".to_string()),
+                (
+                    header::CONTENT_DISPOSITION,
+                    format!("attachment; filename=\"{}.jsonl\"", game_id),
+                ),
+            ];
+            (StatusCode::OK, headers, lines).into_response()
         }
-    });
+        Err(err) => err.into_response(),
+    }
 }
+
+//kept out of the url, which ends up in access logs and the browser history
+fn bearer_token(headers: &HeaderMap) -> GameResult<Uuid> {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .and_then(|token| Uuid::parse_str(token.trim()).ok())
+        .ok_or(GameError::InvalidToken)
+}
+
 fn validate_teams(game: &Game) -> bool {
     let player_count = game.players.len();
 
@@ -155,77 +194,47 @@ fn validate_teams(game: &Game) -> bool {
 }
 
 #[derive(serde::Deserialize)]
-pub(crate) struct JoinTeamBody {
+pub struct JoinTeamBody {
     game_id: String,
-    username: String,
+    //the session token of the player joining the team
+    token: Uuid,
     team: Team,
 }
 
 //TODO: switch to socket.io
-pub(crate) async fn join_team(
+pub async fn join_team(
     app_state: State<AppState>,
     Json(body): Json<JoinTeamBody>,
 ) -> impl IntoResponse {
-    let game_id = body.game_id;
-    let game_store = app_state.game_store.clone();
-    let mut game_lock = game_store.lock().unwrap();
-    let team = body.team;
-
-    let game = game_lock
-        .get_mut(&game_id)
-        .expect("Game should exist at this stage");
-
-    //testing purposes
-    let socket_id = game
-        .players
-        .values()
-        .find(|k| k.username == body.username)
-        .unwrap()
-        .socket_id;
+    let game = match app_state.games.get(&body.game_id) {
+        Ok(game) => game,
+        Err(err) => return err.into_response(),
+    };
 
-    if let Some(player) = game.players.get(&socket_id) {
-        if player.team == Some(team.clone()) {
-            return (StatusCode::BAD_REQUEST, "Player already in team").into_response();
-        }
-    } else {
-        return (StatusCode::BAD_REQUEST, "Player not found").into_response();
-    }
+    //false if the player already is in the team
+    let joined = game
+        .call(move |game, io| {
+            let team = body.team;
 
-    //unwraps are safe because we have already checked if the player exists
-    match team {
-        Team::Spectator => {
-            let username = game
-                .join_team(socket_id, team.clone())
-                .expect("Player should join team");
+            let player_id = game.player_by_token(body.token)?;
 
-            app_state
-                .io
-                .to(game_id)
-                .emit("team-joined", (username, team))
-                .unwrap();
-        }
-        _ => {
-            let team_count = game
-                .players
-                .values()
-                .filter(|p| p.team == Some(team.clone()))
-                .count();
-
-            if team_count >= 2 {
-                return (StatusCode::BAD_REQUEST, "Team is full").into_response();
+            if game.players[&player_id].team == Some(team.clone()) {
+                return Ok(false);
             }
 
-            let username = game
-                .join_team(socket_id, team.clone())
-                .expect("Player should join team");
+            let username = game.join_team(player_id, team.clone())?;
 
-            app_state
-                .io
-                .to(game_id)
+            io.to(body.game_id)
                 .emit("team-joined", (username, team))
-                .unwrap();
-        }
-    };
+                .ok();
+
+            Ok(true)
+        })
+        .await;
 
-    (StatusCode::OK, "Joined team").into_response()
+    match joined {
+        Ok(true) => (StatusCode::OK, "Joined team").into_response(),
+        Ok(false) => (StatusCode::BAD_REQUEST, "Player already in team").into_response(),
+        Err(err) => err.into_response(),
+    }
 }
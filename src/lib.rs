@@ -0,0 +1,15 @@
+use std::sync::Arc;
+
+use crate::game_client::actor::GameRegistry;
+
+pub mod events;
+pub mod game_client;
+pub mod game_core;
+pub mod handlers;
+pub mod simulator;
+
+pub struct State {
+    pub games: GameRegistry,
+}
+
+pub type AppState = Arc<State>;
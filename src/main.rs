@@ -1,42 +1,45 @@
-mod events;
-mod game_client;
-mod game_core;
-mod handlers;
+use std::{sync::Arc, time::Duration};
 
-use std::sync::Arc;
-
-use axum::routing::patch;
+use axum::routing::{get, patch};
 use socketioxide::SocketIo;
 use tower_http::cors::CorsLayer;
 use tracing::info;
 use tracing_subscriber::FmtSubscriber;
 
-use crate::{events::on_connect, game_core::core::GameStore, handlers::start_game};
-
-struct State {
-    io: SocketIo,
-    game_store: GameStore,
-}
-
-type AppState = Arc<State>;
+use tichu_rs::{
+    events::on_connect,
+    game_client::{actor::GameRegistry, client::GracePeriod},
+    handlers::{self, start_game},
+    AppState, State,
+};
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     tracing::subscriber::set_global_default(FmtSubscriber::default())?;
-    let game_store = GameStore::default();
+    let games = GameRegistry::default();
+
+    //seconds a disconnected player has to rejoin before their seat is given up
+    let grace_period = std::env::var("GRACE_PERIOD")
+        .ok()
+        .and_then(|secs| secs.parse().ok())
+        .unwrap_or(60);
 
     let (layer, io) = SocketIo::builder()
-        .with_state(game_store.clone())
+        .with_state(games.clone())
+        .with_state(GracePeriod(Duration::from_secs(grace_period)))
         .build_layer();
 
+    games.bind(io.clone());
     io.ns("/", on_connect);
 
-    let app_state: AppState = Arc::new(State { io, game_store });
+    let app_state: AppState = Arc::new(State { games });
 
     //TODO: map / protect requests -> users -> sockets.id
     let app = axum::Router::new()
         .route("/start", patch(start_game))
         .route("/join_team", patch(handlers::join_team))
+        .route("/state/:game_id", get(handlers::get_state))
+        .route("/log/:game_id", get(handlers::get_log))
         .with_state(app_state)
         .layer(layer)
         .layer(CorsLayer::permissive());
@@ -0,0 +1,183 @@
+use std::collections::HashMap;
+
+use anyhow::anyhow;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use serde::Serialize;
+
+use crate::game_core::{
+    bot,
+    core::{BotKind, Game, GameConfig, GameResult, Team},
+};
+
+/// Plays complete games between two bot strategies, without any sockets involved.
+#[derive(Debug, Clone)]
+pub struct Simulation {
+    /// The strategy of team one and of team two.
+    pub teams: [BotKind; 2],
+    pub games: u32,
+    /// Every game is dealt from its own seed, which is drawn from this one.
+    pub seed: u64,
+    pub config: GameConfig,
+}
+
+impl Default for Simulation {
+    fn default() -> Self {
+        Simulation {
+            teams: [BotKind::Rules, BotKind::Rules],
+            games: 100,
+            seed: 0,
+            config: GameConfig::default(),
+        }
+    }
+}
+
+/// The results of a simulation, team one comes first in every pair.
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Stats {
+    pub games: u32,
+    pub rounds: u32,
+    pub wins: [u32; 2],
+    pub points: [i64; 2],
+    /// Tichu and Grand Tichu calls, and how many of them were made.
+    pub calls: [u32; 2],
+    pub calls_made: [u32; 2],
+}
+
+impl Stats {
+    pub fn win_rate(&self, team: usize) -> f64 {
+        ratio(self.wins[team] as f64, self.games as f64)
+    }
+
+    pub fn average_round_score(&self, team: usize) -> f64 {
+        ratio(self.points[team] as f64, self.rounds as f64)
+    }
+
+    pub fn tichu_success_rate(&self, team: usize) -> f64 {
+        ratio(self.calls_made[team] as f64, self.calls[team] as f64)
+    }
+
+    fn record_round(&mut self, game: &Game) {
+        let round = match game.round.as_ref() {
+            Some(round) => round,
+            None => return,
+        };
+
+        if let Some(summary) = round.summary.as_ref() {
+            self.rounds += 1;
+            self.points[0] += summary.points_t1 as i64;
+            self.points[1] += summary.points_t2 as i64;
+        }
+
+        for player in game.players.values().filter(|p| p.call.is_some()) {
+            let team = match player.team {
+                Some(Team::One) => 0,
+                Some(Team::Two) => 1,
+                _ => continue,
+            };
+
+            self.calls[team] += 1;
+            if round.first_to_finish == Some(player.id) {
+                self.calls_made[team] += 1;
+            }
+        }
+    }
+}
+
+fn ratio(part: f64, total: f64) -> f64 {
+    if total == 0.0 {
+        return 0.0;
+    }
+    part / total
+}
+
+impl Simulation {
+    pub fn run(&self) -> GameResult<Stats> {
+        self.config.validate()?;
+
+        let mut rng = ChaCha20Rng::seed_from_u64(self.seed);
+        let mut stats = Stats::default();
+
+        for _ in 0..self.games {
+            let winner = self.play(rng.gen(), &mut stats)?;
+
+            stats.games += 1;
+            match winner {
+                Team::One => stats.wins[0] += 1,
+                Team::Two => stats.wins[1] += 1,
+                Team::Spectator => return Err(anyhow!("spectators can not win").into()),
+            }
+        }
+
+        Ok(stats)
+    }
+
+    /// Plays a single game until a team wins and returns that team.
+    fn play(&self, seed: u64, stats: &mut Stats) -> GameResult<Team> {
+        let mut game = Game::new(format!("simulation-{}", stats.games), HashMap::new());
+        game.configure(self.config.clone())?;
+        game.seed = seed;
+
+        //bots fill team one first
+        for kind in [self.teams[0], self.teams[0], self.teams[1], self.teams[1]] {
+            game.add_bot(kind)?;
+        }
+
+        game.begin_round()?;
+
+        loop {
+            bot::drive(&mut game)?;
+
+            if !game.round_over() {
+                return Err(anyhow!("bots got stuck in {}", game.game_id).into());
+            }
+
+            stats.record_round(&game);
+
+            if let Some(winner) = game.winner.clone() {
+                return Ok(winner);
+            }
+
+            game.next_round()?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game_core::core::GameConfig;
+
+    use super::Simulation;
+
+    #[test]
+    fn test_simulation() {
+        let simulation = Simulation {
+            games: 3,
+            seed: 42,
+            config: GameConfig {
+                target_score: 200,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let stats = simulation.run().unwrap();
+        assert_eq!(stats.games, 3);
+        assert_eq!(stats.wins.iter().sum::<u32>(), 3);
+        assert!(stats.rounds >= 3);
+        assert!(stats.calls_made[0] <= stats.calls[0]);
+        assert!((stats.win_rate(0) + stats.win_rate(1) - 1.0).abs() < f64::EPSILON);
+
+        //the same seed plays out the same games
+        let again = simulation.run().unwrap();
+        assert_eq!(
+            serde_json::to_value(&stats).unwrap(),
+            serde_json::to_value(&again).unwrap()
+        );
+
+        //every round hands out 100 points plus or minus the calls
+        let points = stats.points.iter().sum::<i64>();
+        assert!(points >= 100 * stats.rounds as i64 - 400 * stats.calls.iter().sum::<u32>() as i64);
+    }
+}